@@ -1,107 +1,911 @@
-use std::fs::File;
-use std::io::{prelude::*, BufReader};
-use std::hash::Hash;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader};
 
 const FILENAME: &str = "./input";
+const SAMPLES: [usize; 6] = [20, 60, 100, 140, 180, 220];
+const SCREEN_WIDTH: usize = 40;
+const SCREEN_HEIGHT: usize = 6;
+const CELL_WIDTH: usize = 5;
+const GLYPH_WIDTH: usize = 4;
 
-#[derive(Copy)]
-#[derive(Clone)]
-#[derive(Eq)]
-#[derive(PartialEq)]
-#[derive(Hash)]
+// The letters the CRT draws, each 4 pixels wide in a 5 pixel wide cell
+const FONT: [(char, [&str; SCREEN_HEIGHT]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+    (' ', ["....", "....", "....", "....", "....", "...."]),
+];
+
+// The registers, X being the one the sprite and signal strength use
+const REGISTERS: [&str; 4] = ["x", "y", "z", "w"];
+const X: usize = 0;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Operand {
+    // A register index
+    Reg,
+    // A literal value
+    Imm,
+}
+
+/*
+ * An entry in the instruction set. Each opcode takes a number of cycles to
+ * complete, and only changes the registers at the end of its last cycle
+ */
+struct Opcode {
+    name: &'static str,
+    operands: &'static [Operand],
+    cycles: usize,
+    exec: fn(&mut [i64; REGISTERS.len()], &[i64]),
+}
+
+const OPCODES: [Opcode; 5] = [
+    Opcode { name: "noop", operands: &[], cycles: 1, exec: |_, _| {} },
+    Opcode { name: "addx", operands: &[Operand::Imm], cycles: 2, exec: |r, a| r[X] += a[0] },
+    Opcode {
+        name: "add", operands: &[Operand::Reg, Operand::Imm], cycles: 2,
+        exec: |r, a| r[a[0] as usize] += a[1]
+    },
+    Opcode {
+        name: "set", operands: &[Operand::Reg, Operand::Imm], cycles: 1,
+        exec: |r, a| r[a[0] as usize] = a[1]
+    },
+    Opcode {
+        name: "mov", operands: &[Operand::Reg, Operand::Reg], cycles: 1,
+        exec: |r, a| r[a[0] as usize] = r[a[1] as usize]
+    },
+];
+
+// An opcode from the table with its operands, registers given by index
+#[derive(Debug,Clone,PartialEq,Eq)]
+struct Instr {
+    opcode: usize,
+    args: Vec<i64>,
+}
+
+impl Instr {
+    fn from_str(line: &str) -> Result<Instr, String> {
+        let mut splits = line.split_whitespace();
+        let name = splits.next().ok_or("Empty instruction")?;
+        let opcode = OPCODES.iter().position(|op| op.name == name)
+            .ok_or_else(|| format!("Illegal instruction '{}'", name))?;
+
+        let args: Vec<&str> = splits.collect();
+        let operands = OPCODES[opcode].operands;
+        if args.len() != operands.len() {
+            return Err(format!("'{}' takes {} operands, not {}", name, operands.len(), args.len()));
+        }
+
+        let args = args.iter().zip(operands)
+            .map(|(arg, kind)| match kind {
+                Operand::Reg => REGISTERS.iter().position(|r| r == arg)
+                    .map(|r| r as i64)
+                    .ok_or_else(|| format!("Unknown register '{}'", arg)),
+                Operand::Imm => arg.parse()
+                    .map_err(|_| format!("Bad value '{}'", arg)),
+            })
+            .collect::<Result<Vec<i64>, String>>()?;
+
+        Ok(Instr { opcode, args })
+    }
+
+    fn cycles(&self) -> usize {
+        OPCODES[self.opcode].cycles
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opcode = &OPCODES[self.opcode];
+        write!(f, "{}", opcode.name)?;
+        for (arg, kind) in self.args.iter().zip(opcode.operands) {
+            match kind {
+                Operand::Reg => write!(f, " {}", REGISTERS[*arg as usize])?,
+                Operand::Imm => write!(f, " {}", arg)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Something that looks at the CPU during every cycle
+trait CycleObserver {
+    fn observe(&mut self, cpu: &Cpu);
+}
+
+/*
+ * Runs a program one cycle at a time. During a cycle, `cycle` is its number
+ * counting from 1 and `pc` is the instruction being executed
+ */
+struct Cpu<'p> {
+    program: &'p [Instr],
+    regs: [i64; REGISTERS.len()],
+    pc: usize,
+    cycle: usize,
+    // cycles already spent on the instruction at pc
+    busy: usize,
+}
+
+impl <'p> Cpu<'p> {
+
+    fn new(program: &'p [Instr]) -> Cpu<'p> {
+        let mut regs = [0; REGISTERS.len()];
+        regs[X] = 1;
+
+        Cpu { program, regs, pc: 0, cycle: 0, busy: 0 }
+    }
+
+    fn x(&self) -> i64 {
+        self.regs[X]
+    }
+
+    fn halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /*
+     * Run one cycle, showing the state during it to each observer before
+     * finishing the instruction if this is its last cycle. Returns false,
+     * without running a cycle, once the program has finished
+     */
+    fn step(&mut self, observers: &mut [&mut dyn CycleObserver]) -> bool {
+        if self.halted() {
+            return false;
+        }
+
+        self.cycle += 1;
+        for o in observers.iter_mut() {
+            o.observe(self);
+        }
+
+        let instr = &self.program[self.pc];
+        self.busy += 1;
+        if self.busy == instr.cycles() {
+            (OPCODES[instr.opcode].exec)(&mut self.regs, &instr.args);
+            self.pc += 1;
+            self.busy = 0;
+        }
+
+        true
+    }
+
+    fn run(&mut self, observers: &mut [&mut dyn CycleObserver]) {
+        while self.step(observers) {}
+    }
+}
+
+// Sums the signal strength, X times the cycle number, during chosen cycles
+struct SignalStrength<'s> {
+    samples: &'s [usize],
+    sum: i64,
+}
+
+impl CycleObserver for SignalStrength<'_> {
+    fn observe(&mut self, cpu: &Cpu) {
+        if self.samples.contains(&cpu.cycle) {
+            self.sum += cpu.cycle as i64 * cpu.x();
+        }
+    }
+}
+
+// Draws a pixel each cycle, lit when the 3 wide sprite centred on X covers it
 #[derive(Debug)]
-enum Instr {
-    Addx(i32),
-    Noop,
+struct Crt {
+    pixels: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
 }
 
-fn instr_from_line(line_str: String) -> Instr {
-    let splits: Vec<&str> = line_str.split(' ').collect();
+impl Crt {
+    fn new() -> Crt {
+        Crt { pixels: [false; SCREEN_WIDTH * SCREEN_HEIGHT] }
+    }
+}
 
-    if splits.len() == 2 && splits[0] == "addx" {
-        let mag: i32 = splits[1].parse().expect("Couldn't parse move magnitude");
+impl CycleObserver for Crt {
+    fn observe(&mut self, cpu: &Cpu) {
+        let pixel = cpu.cycle - 1;
+        if pixel >= self.pixels.len() {
+            return;
+        }
 
-        Instr::Addx(mag)
-    } else if splits.len() == 1 && splits[0] == "noop" {
-        Instr::Noop
+        let column = (pixel % SCREEN_WIDTH) as i64;
+        self.pixels[pixel] = (column - cpu.x()).abs() <= 1;
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.pixels.chunks(SCREEN_WIDTH) {
+            let line: String = row.iter().map(|p| if *p { '#' } else { '.' }).collect();
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Crt {
+    // The pixels of one character cell, row by row
+    fn cell(&self, cell: usize) -> Vec<String> {
+        self.pixels.chunks(SCREEN_WIDTH)
+            .map(|row| row[cell * CELL_WIDTH..(cell + 1) * CELL_WIDTH].iter()
+                .map(|p| if *p { '#' } else { '.' })
+                .collect())
+            .collect()
+    }
+
+    /*
+     * Read the letters on the screen using the font. Any cell which isn't
+     * a letter, including one with its gap column lit, is an error showing
+     * every such cell and the text read with '?' in its place
+     */
+    fn read_letters(&self) -> Result<String, String> {
+        let mut text = String::new();
+        let mut unknown = Vec::new();
+        for cell in 0..SCREEN_WIDTH / CELL_WIDTH {
+            let pixels = self.cell(cell);
+            let letter = FONT.iter()
+                .find(|(_, glyph)| pixels.iter().zip(glyph)
+                    .all(|(row, g)| row[..GLYPH_WIDTH] == **g && row.ends_with('.')))
+                .map(|(c, _)| *c);
+
+            match letter {
+                Some(c) => text.push(c),
+                None => {
+                    text.push('?');
+                    unknown.push(format!("Unrecognised glyph in cell {}:\n{}", cell, pixels.join("\n")));
+                }
+            }
+        }
+
+        if unknown.is_empty() {
+            Ok(text)
+        } else {
+            Err(format!("Read '{}'\n{}", text, unknown.join("\n")))
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Breakpoint {
+    // Before the cycle with this number starts
+    Cycle(usize),
+    // Before the instruction at this index starts
+    Instr(usize),
+}
+
+// Why the debugger gave control back
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Stop {
+    Step,
+    Break(Breakpoint),
+    // X changed from the first value to the second
+    Watch(i64, i64),
+    Halted,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct TraceEntry {
+    cycle: usize,
+    pc: usize,
+    x: i64,
+}
+
+// Records the state during every cycle
+struct Tracer {
+    entries: Vec<TraceEntry>,
+}
+
+impl CycleObserver for Tracer {
+    fn observe(&mut self, cpu: &Cpu) {
+        self.entries.push(TraceEntry { cycle: cpu.cycle, pc: cpu.pc, x: cpu.x() });
+    }
+}
+
+fn trace_header() -> String {
+    format!("{:>5} {:>4}  {:<12} {:>4}", "cycle", "pc", "instruction", "x")
+}
+
+// A line of the trace, with the signal strength on the sampled cycles
+fn trace_line(entry: &TraceEntry, program: &[Instr]) -> String {
+    let line = format!("{:>5} {:>4}  {:<12} {:>4}",
+        entry.cycle, entry.pc, program[entry.pc].to_string(), entry.x);
+    if SAMPLES.contains(&entry.cycle) {
+        format!("{}  signal {}", line, entry.cycle as i64 * entry.x)
     } else {
-        panic!("Illegal instruction?");
+        line
     }
 }
 
-fn part_1() {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+/*
+ * Runs a program under control, stopping at breakpoints and, when X is
+ * watched, whenever it changes or only when it changes to a given value
+ */
+struct Debugger<'p> {
+    cpu: Cpu<'p>,
+    breakpoints: Vec<Breakpoint>,
+    watch: Option<Option<i64>>,
+    tracer: Tracer,
+    // whether the program has been stepped or continued yet
+    started: bool,
+}
+
+impl <'p> Debugger<'p> {
+
+    fn new(program: &'p [Instr]) -> Debugger<'p> {
+        Debugger {
+            cpu: Cpu::new(program),
+            breakpoints: Vec::new(),
+            watch: None,
+            tracer: Tracer { entries: Vec::new() },
+            started: false,
+        }
+    }
+
+    fn at_breakpoint(&self) -> Option<Breakpoint> {
+        self.breakpoints.iter()
+            .find(|b| match b {
+                Breakpoint::Cycle(c) => self.cpu.cycle + 1 == *c,
+                Breakpoint::Instr(i) => self.cpu.busy == 0 && self.cpu.pc == *i,
+            })
+            .copied()
+    }
+
+    // Run a single cycle
+    fn step(&mut self) -> Stop {
+        self.started = true;
+        let old = self.cpu.x();
+        if !self.cpu.step(&mut [&mut self.tracer]) {
+            return Stop::Halted;
+        }
+
+        let new = self.cpu.x();
+        match self.watch {
+            Some(value) if new != old && value.is_none_or(|v| v == new) => Stop::Watch(old, new),
+            _ => Stop::Step,
+        }
+    }
 
-    let mut reg_status = Vec::<i32>::new();
-
-    // append two 1's to remove 0-indexing headache
-    reg_status.push(1);
-    reg_status.push(1);
-
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
-        let instr = instr_from_line(line_str);
-        
-        let reg_val = *reg_status.last().unwrap();
-        match instr {
-            Instr::Noop => reg_status.push(reg_val),
-            Instr::Addx(val) => {
-                reg_status.push(reg_val);
-                reg_status.push(reg_val + val);
+    // Run until something stops the program
+    fn cont(&mut self) -> Stop {
+        // nothing has run yet, so stop before the first cycle if asked to,
+        // but only once so the next continue gets past it
+        if !self.started {
+            self.started = true;
+            if let Some(b) = self.at_breakpoint() {
+                return Stop::Break(b);
+            }
+        }
+
+        loop {
+            let stop = self.step();
+            if stop != Stop::Step {
+                return stop;
+            }
+            if let Some(b) = self.at_breakpoint() {
+                return Stop::Break(b);
             }
         }
     }
 
-    let mut sum = 0;
-    let samples = [20, 60, 100, 140, 180, 220];
-    for i in samples {
-        let strength = reg_status.get(i).unwrap();
-        let strength = i as i32 * strength;
-        
-        //println!("Curing Cycle {}: {}", i, strength);
-        sum += strength;
+    fn describe(&self, stop: Stop) -> String {
+        let state = if self.cpu.halted() {
+            format!("halted after cycle {}, x = {}", self.cpu.cycle, self.cpu.x())
+        } else {
+            format!("before cycle {}, pc {}: {}, x = {}", self.cpu.cycle + 1,
+                self.cpu.pc, self.cpu.program[self.cpu.pc], self.cpu.x())
+        };
+
+        match stop {
+            Stop::Step | Stop::Halted => state,
+            Stop::Break(Breakpoint::Cycle(c)) => format!("Breakpoint at cycle {}, {}", c, state),
+            Stop::Break(Breakpoint::Instr(i)) => format!("Breakpoint at instruction {}, {}", i, state),
+            Stop::Watch(old, new) => format!("X changed from {} to {}, {}", old, new, state),
+        }
     }
 
-    // Print the answer to the first part
-    println!("First Answer: {:?}", sum);
+    /*
+     * Carry out one command, returning what to show for it:
+     *   break cycle N | break instr N | delete | watch [VALUE] | unwatch
+     *   step [N] | continue | print | trace [N]
+     */
+    fn command(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |i: usize| -> Result<usize, String> {
+            let w = words.get(i).ok_or("Missing number")?;
+            w.parse().map_err(|_| format!("Bad number '{}'", w))
+        };
 
-    // Print the answer to the second part
-    let mut screen = vec!['.'; 241];
+        match words.as_slice() {
+            ["break", "cycle", _] => {
+                self.breakpoints.push(Breakpoint::Cycle(number(2)?));
+                Ok(format!("Breakpoint at cycle {}", number(2)?))
+            },
+            ["break", "instr", _] => {
+                self.breakpoints.push(Breakpoint::Instr(number(2)?));
+                Ok(format!("Breakpoint at instruction {}", number(2)?))
+            },
+            ["delete"] => {
+                self.breakpoints.clear();
+                Ok("Deleted all breakpoints".to_string())
+            },
+            ["watch"] => {
+                self.watch = Some(None);
+                Ok("Watching x".to_string())
+            },
+            ["watch", v] => {
+                let v = v.parse().map_err(|_| format!("Bad value '{}'", v))?;
+                self.watch = Some(Some(v));
+                Ok(format!("Watching for x = {}", v))
+            },
+            ["unwatch"] => {
+                self.watch = None;
+                Ok("Not watching x".to_string())
+            },
+            ["step"] | ["step", _] => {
+                let n = if words.len() > 1 { number(1)? } else { 1 };
+                let mut stop = Stop::Step;
+                for _ in 0..n {
+                    stop = self.step();
+                    if stop != Stop::Step {
+                        break;
+                    }
+                }
+                Ok(self.describe(stop))
+            },
+            ["continue"] => {
+                let stop = self.cont();
+                Ok(self.describe(stop))
+            },
+            ["print"] => {
+                let regs: Vec<String> = REGISTERS.iter().zip(self.cpu.regs)
+                    .map(|(name, v)| format!("{} = {}", name, v))
+                    .collect();
+                Ok(format!("{}\n{}", self.describe(Stop::Step), regs.join(", ")))
+            },
+            ["trace"] | ["trace", _] => {
+                let entries = &self.tracer.entries;
+                let n = if words.len() > 1 { number(1)? } else { entries.len() };
+                let mut lines = vec![trace_header()];
+                for entry in &entries[entries.len().saturating_sub(n)..] {
+                    lines.push(trace_line(entry, self.cpu.program));
+                }
+                Ok(lines.join("\n"))
+            },
+            _ => Err(format!("Unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+// Run debugger commands from the input until it ends or says "quit"
+fn debug<R: BufRead, W: Write>(program: &[Instr], input: R, out: &mut W) -> io::Result<()> {
+    let mut debugger = Debugger::new(program);
+    for line in input.lines() {
+        let line = line?;
+        match line.trim() {
+            "" => continue,
+            "quit" => break,
+            _ => match debugger.command(&line) {
+                Ok(s) => writeln!(out, "{}", s)?,
+                Err(e) => writeln!(out, "ERROR: {}", e)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Crt {
+    // A screen drawn as rows of '#' and '.'
+    fn from_str(image: &str) -> Result<Crt, String> {
+        let rows: Vec<&str> = image.lines().filter(|l| !l.trim().is_empty()).collect();
+        if rows.len() != SCREEN_HEIGHT {
+            return Err(format!("Image has {} rows, not {}", rows.len(), SCREEN_HEIGHT));
+        }
+
+        let mut crt = Crt::new();
+        for (r, row) in rows.iter().enumerate() {
+            let row = row.trim();
+            if row.len() != SCREEN_WIDTH {
+                return Err(format!("Row {} is {} pixels wide, not {}", r + 1, row.len(), SCREEN_WIDTH));
+            }
+            for (c, p) in row.chars().enumerate() {
+                crt.pixels[r * SCREEN_WIDTH + c] = match p {
+                    '#' => true,
+                    '.' => false,
+                    _ => return Err(format!("Bad pixel '{}' in row {}", p, r + 1)),
+                };
+            }
+        }
+
+        Ok(crt)
+    }
 
-    for (i, reg) in reg_status.iter().enumerate() {
-        //println!("Cycle {}: {}", i, reg);
-        if i == 0 {
-            // we ignore 0
-            continue;
+    // A screen with the text drawn in the font, one letter per cell
+    fn from_text(text: &str) -> Result<Crt, String> {
+        if text.chars().count() > SCREEN_WIDTH / CELL_WIDTH {
+            return Err(format!("'{}' won't fit on the screen", text));
         }
 
-        // check if "sprite" covers current pixel
-        let x = (i as i32) % 40;
-        if (x - reg) >=0 && (x - reg) < 3 {
-            screen[i] = '#';
+        let mut crt = Crt::new();
+        for (cell, letter) in text.chars().enumerate() {
+            let (_, glyph) = FONT.iter().find(|(c, _)| *c == letter.to_ascii_uppercase())
+                .ok_or_else(|| format!("No glyph for '{}'", letter))?;
+            for (r, row) in glyph.iter().enumerate() {
+                for (c, p) in row.chars().enumerate() {
+                    crt.pixels[r * SCREEN_WIDTH + cell * CELL_WIDTH + c] = p == '#';
+                }
+            }
         }
+
+        Ok(crt)
     }
+}
+
+// Values of X worth trying, including one just off each side of the screen
+const X_MIN: i64 = -2;
+const X_MAX: i64 = SCREEN_WIDTH as i64 + 1;
 
-    let mut screen_str = String::new();
-    for (i, c) in screen.iter().enumerate() {
-        if i == 0 {
-            // we ignore 0
-            continue;
+/*
+ * Write a program of addx and noop which draws the image. X can only
+ * change at the end of an addx, so each value it takes must last for at
+ * least the 2 cycles of the addx that ends it, the first value being 1.
+ * Going pixel by pixel, keep the fewest changes of X which reach each
+ * value with the current run either 1 or at least 2 cycles long, then
+ * write each run of X out as noops and an addx. The program is run to
+ * check it draws the image
+ */
+fn assemble(target: &Crt) -> Result<Vec<Instr>, String> {
+    let values = (X_MAX - X_MIN + 1) as usize;
+    let lit = |pixel: usize, x: i64| ((pixel % SCREEN_WIDTH) as i64 - x).abs() <= 1;
+
+    // for each pixel and (value, long run) state, the changes so far and previous state
+    let state = |x: i64, long: bool| (x - X_MIN) as usize * 2 + long as usize;
+    let mut best: Vec<Vec<Option<(usize, usize)>>> = vec![vec![None; values * 2]; target.pixels.len()];
+    if lit(0, 1) != target.pixels[0] {
+        return Err("No program can draw this image, X starts at 1".to_string());
+    }
+    best[0][state(1, false)] = Some((0, 0));
+
+    for pixel in 1..target.pixels.len() {
+        for x in X_MIN..=X_MAX {
+            for long in [false, true] {
+                let Some((changes, _)) = best[pixel - 1][state(x, long)] else { continue };
+
+                // carry on with the same value, or start a new one after a long run
+                let next = (X_MIN..=X_MAX)
+                    .filter(|n| *n == x || long)
+                    .map(|n| if n == x { (n, true, changes) } else { (n, false, changes + 1) });
+                for (n, n_long, n_changes) in next {
+                    if lit(pixel, n) != target.pixels[pixel] {
+                        continue;
+                    }
+                    let slot = &mut best[pixel][state(n, n_long)];
+                    if slot.is_none_or(|(c, _)| n_changes < c) {
+                        *slot = Some((n_changes, state(x, long)));
+                    }
+                }
+            }
         }
-        screen_str.push(*c);
-        if i % 40 == 0 {
-            screen_str.push('\n');
+
+        if best[pixel].iter().all(|s| s.is_none()) {
+            return Err(format!("No program can draw this image past row {}, column {}",
+                pixel / SCREEN_WIDTH + 1, pixel % SCREEN_WIDTH + 1));
         }
     }
-    println!("{}", screen_str);
+
+    // follow the fewest changes back to find X during every cycle
+    let last = &best[target.pixels.len() - 1];
+    let mut s = (0..last.len()).filter(|s| last[*s].is_some())
+        .min_by_key(|s| last[*s].unwrap().0)
+        .unwrap();
+    let mut xs = Vec::new();
+    for pixel in (0..target.pixels.len()).rev() {
+        xs.push(X_MIN + (s / 2) as i64);
+        s = best[pixel][s].unwrap().1;
+    }
+    xs.reverse();
+
+    let mut program = Vec::new();
+    let mut start = 0;
+    while start < xs.len() {
+        let end = (start..xs.len()).find(|i| xs[*i] != xs[start]).unwrap_or(xs.len());
+        if end < xs.len() {
+            for _ in start..end - 2 {
+                program.push(Instr::from_str("noop")?);
+            }
+            program.push(Instr::from_str(&format!("addx {}", xs[end] - xs[start]))?);
+        } else {
+            for _ in start..end {
+                program.push(Instr::from_str("noop")?);
+            }
+        }
+        start = end;
+    }
+
+    let (_, crt) = run_program(&program);
+    if crt.pixels != target.pixels {
+        return Err(format!("Program draws the wrong image:\n{}", crt));
+    }
+
+    Ok(program)
+}
+
+fn read_program(filename: &str) -> Result<Vec<Instr>, String> {
+    // Open the file
+    let file = File::open(filename).unwrap();
+    let reader = BufReader::new(file);
+
+    reader.lines()
+        .map(|line| line.expect("Couldn't read line as string?"))
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Instr::from_str(&line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+// Run the program once, sampling the signal strength and drawing the screen
+fn run_program(program: &[Instr]) -> (i64, Crt) {
+    let mut signal = SignalStrength { samples: &SAMPLES, sum: 0 };
+    let mut crt = Crt::new();
+    Cpu::new(program).run(&mut [&mut signal, &mut crt]);
+
+    (signal.sum, crt)
+}
+
+fn part_1(program: &[Instr]) {
+    let (sum, _) = run_program(program);
+
+    // Print the answer to the first part
+    println!("First Answer: {:?}", sum);
+}
+
+fn part_2(program: &[Instr]) {
+    let (_, crt) = run_program(program);
+
+    // Print the answer to the second part
+    match crt.read_letters() {
+        Ok(text) => println!("Second Answer: {}", text),
+        Err(e) => println!("Second Answer: {}\n{}", e, crt),
+    }
 }
 
 fn main() {
+    // "assemble image FILE" or "assemble text TEXT" prints a program which
+    // draws the image in the file or the text
+    let args: Vec<String> = env::args().collect();
+    let mode = args.get(1).map(|a| a.as_str());
+    if mode == Some("assemble") {
+        let arg = args.get(3).expect("Usage: assemble image FILE | assemble text TEXT");
+        let target = match args.get(2).map(|a| a.as_str()) {
+            Some("image") => Crt::from_str(&fs::read_to_string(arg).expect("Couldn't read image")),
+            Some("text") => Crt::from_text(arg),
+            _ => Err("Usage: assemble image FILE | assemble text TEXT".to_string()),
+        };
+        match target.and_then(|t| assemble(&t)) {
+            Ok(program) => program.iter().for_each(|i| println!("{}", i)),
+            Err(e) => println!("ERROR: {}", e),
+        }
+        return;
+    }
+
     println!("Advent of Code, Day 10");
 
-    part_1();
+    // "debug [file]" runs debugger commands from stdin, "trace [file]"
+    // prints the state during every cycle
+    let filename = match mode {
+        Some(_) => args.get(2).map(|f| f.as_str()).unwrap_or(FILENAME),
+        None => FILENAME
+    };
+    let program = match read_program(filename) {
+        Ok(program) => program,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+
+    match mode {
+        Some("debug") => {
+            debug(&program, io::stdin().lock(), &mut io::stdout()).expect("Couldn't run debugger");
+        },
+        Some("trace") => {
+            let mut tracer = Tracer { entries: Vec::new() };
+            Cpu::new(&program).run(&mut [&mut tracer]);
+            println!("{}", trace_header());
+            for entry in &tracer.entries {
+                println!("{}", trace_line(entry, &program));
+            }
+        },
+        Some(m) => println!("Unknown mode: {}", m),
+        None => {
+            part_1(&program);
+            part_2(&program);
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_example() {
+        let program = read_program("./test").unwrap();
+        let (sum, crt) = run_program(&program);
+        assert_eq!(sum, 13140);
+        assert_eq!(crt.to_string(), "\
+##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....
+");
+
+        let (sum, _) = run_program(&read_program("./input").unwrap());
+        assert_eq!(sum, 15220);
+    }
+
+    #[test]
+    fn test_assemble() {
+        // the input's own image, written out and read back
+        let (_, crt) = run_program(&read_program("./input").unwrap());
+        let target = Crt::from_str(&crt.to_string()).unwrap();
+        let program = assemble(&target).unwrap();
+        let (_, drawn) = run_program(&program);
+        assert_eq!(drawn.to_string(), crt.to_string());
+        assert_eq!(program.iter().map(|i| i.cycles()).sum::<usize>(), 240);
+
+        for text in ["RFZEKBFA", "FUR BELL", "ZAP", "EGGS", "BULK"] {
+            let program = assemble(&Crt::from_text(text).unwrap()).unwrap();
+            let (_, drawn) = run_program(&program);
+            assert_eq!(drawn.read_letters().unwrap().trim_end(), text);
+        }
+
+        // X starts at 1, so the first two pixels are always lit
+        assert_eq!(assemble(&Crt::from_text("ABC").unwrap()).unwrap_err(),
+            "No program can draw this image, X starts at 1");
+        assert_eq!(assemble(&Crt::from_text("KEG").unwrap()).unwrap_err(),
+            "No program can draw this image past row 1, column 2");
+
+        assert_eq!(Crt::from_text("LONG TEXT").unwrap_err(), "'LONG TEXT' won't fit on the screen");
+        assert_eq!(Crt::from_text("EQ").unwrap_err(), "No glyph for 'Q'");
+        assert_eq!(Crt::from_str("##\n").unwrap_err(), "Image has 1 rows, not 6");
+    }
+
+    #[test]
+    fn test_read_letters() {
+        let (_, crt) = run_program(&read_program("./input").unwrap());
+        assert_eq!(crt.read_letters(), Ok("RFZEKBFA".to_string()));
+
+        // every letter of the font can be read back
+        let mut crt = Crt::new();
+        for (i, (_, glyph)) in FONT[..SCREEN_WIDTH / CELL_WIDTH].iter().enumerate() {
+            for (row, line) in glyph.iter().enumerate() {
+                for (col, c) in line.chars().enumerate() {
+                    crt.pixels[row * SCREEN_WIDTH + i * CELL_WIDTH + col] = c == '#';
+                }
+            }
+        }
+        assert_eq!(crt.read_letters(), Ok("ABCEFGHI".to_string()));
+
+        // a lit gap column spoils a letter
+        crt.pixels[SCREEN_WIDTH + 4] = true;
+        let e = crt.read_letters().unwrap_err();
+        assert!(e.starts_with("Read '?BCEFGHI'\nUnrecognised glyph in cell 0:\n.##..\n#..##\n"));
+
+        let (_, crt) = run_program(&read_program("./test").unwrap());
+        let e = crt.read_letters().unwrap_err();
+        assert!(e.starts_with("Read '????????'\n"));
+        assert_eq!(e.matches("Unrecognised").count(), 8);
+    }
+
+    #[test]
+    fn test_cpu() {
+        let program: Vec<Instr> = ["noop", "addx 3", "addx -5", "set y 7", "add y -2", "mov x y"]
+            .iter()
+            .map(|l| Instr::from_str(l).unwrap())
+            .collect();
+        assert_eq!(program[4].to_string(), "add y -2");
+
+        let mut tracer = Tracer { entries: Vec::new() };
+        let mut cpu = Cpu::new(&program);
+        cpu.run(&mut [&mut tracer]);
+        let trace: Vec<(usize, usize, i64)> = tracer.entries.iter()
+            .map(|e| (e.cycle, e.pc, e.x))
+            .collect();
+        assert_eq!(trace, vec![
+            (1, 0, 1), (2, 1, 1), (3, 1, 1), (4, 2, 4), (5, 2, 4),
+            (6, 3, -1), (7, 4, -1), (8, 4, -1), (9, 5, -1)
+        ]);
+        assert_eq!(cpu.regs, [5, 5, 0, 0]);
+        assert!(cpu.halted());
+        assert!(!cpu.step(&mut []));
+
+        assert_eq!(Instr::from_str("jmp 3"), Err("Illegal instruction 'jmp'".to_string()));
+        assert_eq!(Instr::from_str("add q 1"), Err("Unknown register 'q'".to_string()));
+        assert_eq!(Instr::from_str("addx"), Err("'addx' takes 1 operands, not 0".to_string()));
+        assert_eq!(Instr::from_str("addx one"), Err("Bad value 'one'".to_string()));
+    }
+
+    #[test]
+    fn test_debugger() {
+        let program = read_program("./test").unwrap();
+        let script = "\
+break cycle 20
+break instr 3
+continue
+continue
+trace 2
+delete
+watch 21
+continue
+unwatch
+step 3
+print
+jump
+continue
+quit
+print
+";
+        let mut out = Vec::new();
+        debug(&program, script.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+Breakpoint at cycle 20
+Breakpoint at instruction 3
+Breakpoint at instruction 3, before cycle 7, pc 3: addx -3, x = 11
+Breakpoint at cycle 20, before cycle 20, pc 10: addx -1, x = 21
+cycle   pc  instruction     x
+   18    8  addx 4         17
+   19    9  noop           21
+Deleted all breakpoints
+Watching for x = 21
+X changed from 30 to 21, before cycle 140, pc 85: addx 1, x = 21
+Not watching x
+before cycle 143, pc 86: addx 1, x = 22
+before cycle 143, pc 86: addx 1, x = 22
+x = 22, y = 0, z = 0, w = 0
+ERROR: Unknown command 'jump'
+halted after cycle 240, x = 17
+");
+
+        // breakpoints before anything has run stop the first continue only
+        let script = "\
+break cycle 1
+break instr 0
+continue
+continue
+";
+        let mut out = Vec::new();
+        debug(&program, script.as_bytes(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+Breakpoint at cycle 1
+Breakpoint at instruction 0
+Breakpoint at cycle 1, before cycle 1, pc 0: addx 15, x = 1
+halted after cycle 240, x = 17
+");
+
+        let mut out = Vec::new();
+        debug(&program, "break instr 0\ncontinue\n".as_bytes(), &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap()
+            .ends_with("Breakpoint at instruction 0, before cycle 1, pc 0: addx 15, x = 1\n"));
+    }
+}
@@ -1,262 +1,801 @@
-use std::collections::VecDeque;
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::mem;
+
+const FILENAME: &str = "./input";
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Operand {
+    Old,
+    Num(u64),
+}
 
-const ROUNDS: i32 = 10000;
-const GCD: i64 = 19 * 2 * 13 * 5 * 7 * 11 * 17 * 3;
+impl Operand {
+    fn from_str(s: &str) -> Result<Operand, String> {
+        match s {
+            "old" => Ok(Operand::Old),
+            _ => s.parse().map(Operand::Num).map_err(|_| format!("Bad operand '{}'", s)),
+        }
+    }
 
-struct Monkey {
-    items: VecDeque::<i64>,
-    business: i64,
-    test_div: i64,
-    true_monkey: usize,
-    false_monkey: usize,
-    mut_func: fn(item: i64) -> i64
+    fn value<W: Worry>(&self, old: &W) -> W {
+        match self {
+            Operand::Old => old.clone(),
+            Operand::Num(n) => W::from_u64(*n),
+        }
+    }
 }
 
-impl Monkey {
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Operator {
+    Add,
+    Mul,
+}
+
+// The right hand side of "new = old * 13"
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct Operation {
+    lhs: Operand,
+    op: Operator,
+    rhs: Operand,
+}
+
+impl Operation {
+    fn from_str(s: &str) -> Result<Operation, String> {
+        let splits: Vec<&str> = s.split_whitespace().collect();
+        if splits.len() != 3 {
+            return Err(format!("Operation '{}' is not 'a op b'", s));
+        }
+
+        let op = match splits[1] {
+            "+" => Operator::Add,
+            "*" => Operator::Mul,
+            _ => return Err(format!("Unknown operator '{}'", splits[1])),
+        };
+
+        Ok(Operation {
+            lhs: Operand::from_str(splits[0])?,
+            op,
+            rhs: Operand::from_str(splits[2])?,
+        })
+    }
+
+    // The new worry level, or None if it is too big to hold
+    fn apply<W: Worry>(&self, old: &W) -> Option<W> {
+        let (lhs, rhs) = (self.lhs.value(old), self.rhs.value(old));
+        match self.op {
+            Operator::Add => lhs.add(&rhs),
+            Operator::Mul => lhs.mul(&rhs),
+        }
+    }
+}
+
+// A worry level, which may be too big for some representations
+trait Worry: Clone + fmt::Display {
+    fn from_u64(n: u64) -> Self;
+    fn add(&self, other: &Self) -> Option<Self>;
+    fn mul(&self, other: &Self) -> Option<Self>;
+    fn div(&self, d: u64) -> Self;
+    fn rem(&self, d: u64) -> u64;
+}
+
+impl Worry for u64 {
+    fn from_u64(n: u64) -> u64 {
+        n
+    }
+
+    fn add(&self, other: &u64) -> Option<u64> {
+        self.checked_add(*other)
+    }
+
+    fn mul(&self, other: &u64) -> Option<u64> {
+        self.checked_mul(*other)
+    }
+
+    fn div(&self, d: u64) -> u64 {
+        self / d
+    }
+
+    fn rem(&self, d: u64) -> u64 {
+        self % d
+    }
+}
+
+// An unsigned integer of any size, as base 2^32 digits, least significant first
+#[derive(Debug,Clone,PartialEq,Eq)]
+struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    fn trim(mut self) -> BigUint {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+
+        self
+    }
 
-    fn take_turn(&mut self) -> (VecDeque<i64>, VecDeque<i64>) {
-        let mut true_rval = VecDeque::<i64>::new();
-        let mut false_rval = VecDeque::<i64>::new();
-
-        while !self.items.is_empty() {
-            let mut item = self.items.pop_front().unwrap();
-            //println!("  Monkey inspects an item with a worry level of {}.", item);
-            self.business += 1;
-
-            item = (self.mut_func)(item);
-            item %= GCD;
-            //println!("    Worry level is mutated to {}.", item);
-
-            //item /= 3;
-            //println!("    Monkey gets bored with item. Worry level is divided by 3 to {}.", item);
-
-            if item % self.test_div == 0 {
-                //println!("    Current worry level is divisible by {}.", self.test_div);
-                true_rval.push_back(item);
-                //println!("    Item with worry level {} is thrown to monkey {}.", item, self.true_monkey);
-            } else {
-                //println!("    Current worry level is not divisible by {}.", self.test_div);
-                false_rval.push_back(item);
-                //println!("    Item with worry level {} is thrown to monkey {}.", item, self.false_monkey);
+    // The quotient and remainder after dividing by d
+    fn div_rem(&self, d: u64) -> (BigUint, u64) {
+        assert!(d > 0, "Division by zero");
+
+        let mut quotient = vec![0; self.digits.len()];
+        let mut rem: u128 = 0;
+        for (i, digit) in self.digits.iter().enumerate().rev() {
+            let n = (rem << 32) | *digit as u128;
+            quotient[i] = (n / d as u128) as u32;
+            rem = n % d as u128;
+        }
+
+        (BigUint { digits: quotient }.trim(), rem as u64)
+    }
+}
+
+impl Worry for BigUint {
+    fn from_u64(n: u64) -> BigUint {
+        BigUint { digits: vec![n as u32, (n >> 32) as u32] }.trim()
+    }
+
+    fn add(&self, other: &BigUint) -> Option<BigUint> {
+        let mut digits = Vec::with_capacity(self.digits.len().max(other.digits.len()) + 1);
+        let mut carry = 0;
+        for i in 0..self.digits.len().max(other.digits.len()) {
+            let sum = *self.digits.get(i).unwrap_or(&0) as u64
+                + *other.digits.get(i).unwrap_or(&0) as u64
+                + carry;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+
+        Some(BigUint { digits }.trim())
+    }
+
+    fn mul(&self, other: &BigUint) -> Option<BigUint> {
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0;
+            for (j, b) in other.digits.iter().enumerate() {
+                let n = *a as u64 * *b as u64 + digits[i + j] as u64 + carry;
+                digits[i + j] = n as u32;
+                carry = n >> 32;
             }
+            digits[i + other.digits.len()] = carry as u32;
         }
 
-        (true_rval, false_rval)
+        Some(BigUint { digits }.trim())
+    }
+
+    fn div(&self, d: u64) -> BigUint {
+        self.div_rem(d).0
+    }
+
+    fn rem(&self, d: u64) -> u64 {
+        self.div_rem(d).1
     }
 }
 
-#[allow(dead_code)]
-fn test0_mut(item: i64) -> i64 {
-    item * 19
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // take 9 decimal digits at a time from the bottom
+        let mut chunks = Vec::new();
+        let mut n = self.clone();
+        while !n.digits.is_empty() {
+            let (q, r) = n.div_rem(1_000_000_000);
+            chunks.push(r);
+            n = q;
+        }
+
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((top, rest)) => {
+                write!(f, "{}", top)?;
+                for chunk in rest.iter().rev() {
+                    write!(f, "{:09}", chunk)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
-#[allow(dead_code)]
-fn test1_mut(item: i64) -> i64 {
-    item + 6
+struct Monkey {
+    items: Vec<u64>,
+    test_div: u64,
+    true_monkey: usize,
+    false_monkey: usize,
+    operation: Operation
 }
 
-#[allow(dead_code)]
-fn test2_mut(item: i64) -> i64 {
-    item * item
+// The text of a line after its expected prefix, ignoring indentation
+fn field<'a>(line: Option<&&'a str>, prefix: &str) -> Result<&'a str, String> {
+    let line = line.ok_or_else(|| format!("Missing '{}' line", prefix))?.trim();
+
+    line.strip_prefix(prefix)
+        .map(|rest| rest.trim())
+        .ok_or_else(|| format!("Expected '{}' but found '{}'", prefix, line))
 }
 
-#[allow(dead_code)]
-fn test3_mut(item: i64) -> i64 {
-    item + 3
+fn number<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("Bad number '{}'", s))
 }
 
-#[allow(dead_code)]
-fn test_monkeys() -> Vec::<Monkey> {
-    let test_0 = Monkey {
-      items: VecDeque::from([79, 98]),
-      business: 0,
-      mut_func: test0_mut,
-      test_div: 23,
-      true_monkey: 2,
-      false_monkey: 3
-    };
+impl Monkey {
 
-    let test_1 = Monkey {
-      items: VecDeque::from([54, 65, 75, 74]),
-      business: 0,
-      mut_func: test1_mut, // Operation: new = old + 6
-      test_div: 19,
-      true_monkey: 2,
-      false_monkey: 0
-    };
+    /*
+     * One monkey's definition, with its number:
+     *   Monkey 0:
+     *     Starting items: 79, 98
+     *     Operation: new = old * 19
+     *     Test: divisible by 23
+     *       If true: throw to monkey 2
+     *       If false: throw to monkey 3
+     */
+    fn from_lines(lines: &[&str]) -> Result<(usize, Monkey), String> {
+        let mut lines = lines.iter();
+
+        let id = field(lines.next(), "Monkey")?;
+        let id = number(id.strip_suffix(':').ok_or_else(|| format!("Expected ':' after 'Monkey {}'", id))?)?;
+
+        let items = field(lines.next(), "Starting items:")?;
+        let items = items.split(',')
+            .map(|i| i.trim())
+            .filter(|i| !i.is_empty())
+            .map(number)
+            .collect::<Result<Vec<u64>, String>>()?;
+
+        let operation = Operation::from_str(field(lines.next(), "Operation: new =")?)?;
+        let test_div = number(field(lines.next(), "Test: divisible by")?)?;
+        if test_div == 0 {
+            return Err(format!("Monkey {} can't test divisibility by {}", id, test_div));
+        }
+        let true_monkey = number(field(lines.next(), "If true: throw to monkey")?)?;
+        let false_monkey = number(field(lines.next(), "If false: throw to monkey")?)?;
 
-    let test_2 = Monkey {
-      items: VecDeque::from([79, 60, 97]),
-      business: 0,
-      mut_func: test2_mut, // Operation: new = old * old
-      test_div: 13,
-      true_monkey: 1,
-      false_monkey: 3
-    };
+        if let Some(extra) = lines.next() {
+            return Err(format!("Unexpected line '{}' in monkey {}", extra.trim(), id));
+        }
 
-    let test_3 = Monkey {
-      items: VecDeque::from([74]),
-      business: 0,
-      mut_func: test3_mut, // Operation: new = old + 3
-      test_div: 17,
-      true_monkey: 0,
-      false_monkey: 1
-    };
+        Ok((id, Monkey { items, test_div, true_monkey, false_monkey, operation }))
+    }
+}
+
+/*
+ * Read every monkey, separated by blank lines. The monkeys must be numbered
+ * in order from 0 and only throw to each other
+ */
+fn read_monkeys(filename: &str) -> Result<Vec<Monkey>, String> {
+    let input = fs::read_to_string(filename)
+        .map_err(|e| format!("Couldn't read {}: {}", filename, e))?;
+
+    let mut monkeys = Vec::new();
+    let lines: Vec<&str> = input.lines().collect();
+    for block in lines.split(|l| l.trim().is_empty()).filter(|b| !b.is_empty()) {
+        let (id, monkey) = Monkey::from_lines(block)?;
+        if id != monkeys.len() {
+            return Err(format!("Expected monkey {} but found monkey {}", monkeys.len(), id));
+        }
+        monkeys.push(monkey);
+    }
+
+    for (i, m) in monkeys.iter().enumerate() {
+        if m.true_monkey >= monkeys.len() || m.false_monkey >= monkeys.len() {
+            return Err(format!("Monkey {} throws to a monkey that isn't there", i));
+        }
+    }
 
-    vec![test_0, test_1, test_2, test_3]
+    Ok(monkeys)
 }
 
-fn input0_mut(item: i64) -> i64 {
-    item * 13
+// What happens to an item's worry level after each inspection
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Relief {
+    None,
+    // Relief that the item wasn't damaged divides the worry level
+    DivideBy(u64),
 }
 
-fn input1_mut(item: i64) -> i64 {
-    item + 3
+// How worry levels are kept from growing without limit
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Modulus {
+    // Not at all, failing if they overflow
+    None,
+    // Modulo the product of the divisors
+    Product,
+    // Modulo the lowest common multiple of the divisors
+    Lcm,
+    // Not at all, with worry levels of any size
+    BigInt,
 }
 
-fn input2_mut(item: i64) -> i64 {
-    item + 6
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct Config {
+    rounds: usize,
+    relief: Relief,
+    modulus: Modulus,
 }
 
-fn input3_mut(item: i64) -> i64 {
-    item + 2
+const PART_1: Config = Config { rounds: 20, relief: Relief::DivideBy(3), modulus: Modulus::None };
+const PART_2: Config = Config { rounds: 10000, relief: Relief::None, modulus: Modulus::Lcm };
+
+impl Config {
+    fn from_args(rounds: &str, relief: &str, modulus: &str) -> Result<Config, String> {
+        let rounds = number(rounds)?;
+        let relief = match relief {
+            "none" => Relief::None,
+            _ => Relief::DivideBy(number(relief)?),
+        };
+        let modulus = match modulus {
+            "none" => Modulus::None,
+            "product" => Modulus::Product,
+            "lcm" => Modulus::Lcm,
+            "bigint" => Modulus::BigInt,
+            _ => return Err(format!("Unknown modulus '{}'", modulus)),
+        };
+
+        Ok(Config { rounds, relief, modulus })
+    }
 }
 
-fn input4_mut(item: i64) -> i64 {
-    item * item
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
 }
 
-fn input5_mut(item: i64) -> i64 {
-    item + 4
+/*
+ * How many items each monkey inspected. When the history is kept, also
+ * what happened in each round: how many items each monkey inspected, how
+ * many it held at the end of the round and how many it threw to each
+ * monkey. When items are tracked, every throw of each item is kept too,
+ * the items being numbered in the order they start
+ */
+struct Stats {
+    monkeys: usize,
+    business: Vec<u64>,
+    history: bool,
+    inspections: Vec<Vec<u64>>,
+    queues: Vec<Vec<usize>>,
+    // items thrown from a to b at a * monkeys + b
+    throws: Vec<Vec<u64>>,
+    // (round, from, to) for every throw
+    paths: Option<Vec<Vec<(usize, usize, usize)>>>,
 }
 
-fn input6_mut(item: i64) -> i64 {
-    item * 7
+impl Stats {
+    fn new(monkeys: &[Monkey], history: bool, track_items: bool) -> Stats {
+        let items = monkeys.iter().map(|m| m.items.len()).sum();
+
+        Stats {
+            monkeys: monkeys.len(),
+            business: vec![0; monkeys.len()],
+            history,
+            inspections: Vec::new(),
+            queues: Vec::new(),
+            throws: Vec::new(),
+            paths: if track_items { Some(vec![Vec::new(); items]) } else { None },
+        }
+    }
+
+    fn rounds_csv(&self) -> String {
+        let mut csv = String::from("round,monkey,inspections,queue\n");
+        for (r, (inspections, queues)) in self.inspections.iter().zip(&self.queues).enumerate() {
+            for (m, (i, q)) in inspections.iter().zip(queues).enumerate() {
+                csv.push_str(&format!("{},{},{},{}\n", r + 1, m, i, q));
+            }
+        }
+
+        csv
+    }
+
+    // Only the pairs of monkeys with items thrown between them
+    fn throws_csv(&self) -> String {
+        let mut csv = String::from("round,from,to,items\n");
+        for (r, throws) in self.throws.iter().enumerate() {
+            for (pair, n) in throws.iter().enumerate().filter(|(_, n)| **n > 0) {
+                csv.push_str(&format!("{},{},{},{}\n", r + 1, pair / self.monkeys, pair % self.monkeys, n));
+            }
+        }
+
+        csv
+    }
+
+    fn items_csv(&self) -> Option<String> {
+        let paths = self.paths.as_ref()?;
+        let mut csv = String::from("item,round,from,to\n");
+        for (item, path) in paths.iter().enumerate() {
+            for (round, from, to) in path {
+                csv.push_str(&format!("{},{},{},{}\n", item, round, from, to));
+            }
+        }
+
+        Some(csv)
+    }
 }
 
-fn input7_mut(item: i64) -> i64 {
-    item + 7
+/*
+ * Play the rounds, keeping the stats of each. Every test gives the same
+ * answer for worry levels modulo a multiple of all the divisors, but
+ * dividing after taking the modulus doesn't, so relief can't be used
+ * with one
+ */
+fn record(monkeys: &[Monkey], config: &Config, history: bool, track_items: bool) -> Result<Stats, String> {
+    let modulus = modulus(monkeys, config)?;
+    let mut stats = Stats::new(monkeys, history, track_items);
+    match config.modulus {
+        Modulus::BigInt => play::<BigUint>(monkeys, config, modulus, &mut stats)?,
+        _ => play::<u64>(monkeys, config, modulus, &mut stats)?,
+    }
+
+    Ok(stats)
 }
 
-fn input_monkeys() -> Vec::<Monkey> {
-    let monkey_0 = Monkey {
-        items: VecDeque::from([71, 86]),
-        business: 0,
-        mut_func: input0_mut, // Operation: new = old * 13
-        test_div: 19,
-        true_monkey: 6,
-        false_monkey: 7
+// The modulus to keep worry levels to, checking it can be used
+fn modulus(monkeys: &[Monkey], config: &Config) -> Result<Option<u64>, String> {
+    let divisors = monkeys.iter().map(|m| m.test_div);
+    let overflow = || "The modulus is too big".to_string();
+    let modulus = match config.modulus {
+        Modulus::None | Modulus::BigInt => None,
+        Modulus::Product => Some(divisors.map(Some).reduce(|a, b| a?.checked_mul(b?))
+            .flatten().ok_or_else(overflow)?),
+        Modulus::Lcm => Some(divisors.map(Some).reduce(|a, b| (a? / gcd(a?, b?)).checked_mul(b?))
+            .flatten().ok_or_else(overflow)?),
     };
+    if modulus.is_some() && config.relief != Relief::None {
+        return Err("Relief can't be used with a modulus".to_string());
+    }
+    if config.relief == Relief::DivideBy(0) {
+        return Err("Relief can't divide by 0".to_string());
+    }
 
-    let monkey_1 = Monkey {
-        items: VecDeque::from([66, 50, 90, 53, 88, 85]),
-        business: 0,
-        mut_func: input1_mut, // Operation: new = old + 3
-        test_div: 2,
-        true_monkey: 5,
-        false_monkey: 4
-    };
+    Ok(modulus)
+}
 
-    let monkey_2 = Monkey {
-        items: VecDeque::from([97, 54, 89, 62, 84, 80, 63]),
-        business: 0,
-        mut_func: input2_mut, // Operation: new = old + 6
-        test_div: 13,
-        true_monkey: 4,
-        false_monkey: 1
-    };
+fn play<W: Worry>(
+        monkeys: &[Monkey],
+        config: &Config,
+        modulus: Option<u64>,
+        stats: &mut Stats) -> Result<(), String> {
+    // items are numbered so they can be tracked
+    let mut queues: Vec<VecDeque<(usize, W)>> = Vec::new();
+    let mut start = 0;
+    for m in monkeys {
+        queues.push(m.items.iter().enumerate().map(|(i, w)| (start + i, W::from_u64(*w))).collect());
+        start += m.items.len();
+    }
 
-    let monkey_3 = Monkey {
-        items: VecDeque::from([82, 97, 56, 92]),
-        business: 0,
-        mut_func: input3_mut, // Operation: new = old + 2
-        test_div: 5,
-        true_monkey: 6,
-        false_monkey: 0
-    };
+    for round in 1..=config.rounds {
+        let mut inspections = vec![0; monkeys.len()];
+        let mut throws = if stats.history { vec![0; monkeys.len() * monkeys.len()] } else { Vec::new() };
+
+        for (i, monkey) in monkeys.iter().enumerate() {
+            for (id, item) in mem::take(&mut queues[i]) {
+                inspections[i] += 1;
+
+                let mut item = monkey.operation.apply(&item)
+                    .ok_or_else(|| format!("Worry level overflowed in round {}", round))?;
+                if let Relief::DivideBy(d) = config.relief {
+                    item = item.div(d);
+                }
+                if let Some(m) = modulus {
+                    item = W::from_u64(item.rem(m));
+                }
+
+                let target = if item.rem(monkey.test_div) == 0 {
+                    monkey.true_monkey
+                } else {
+                    monkey.false_monkey
+                };
+                if stats.history {
+                    throws[i * monkeys.len() + target] += 1;
+                }
+                if let Some(paths) = stats.paths.as_mut() {
+                    paths[id].push((round, i, target));
+                }
+                queues[target].push_back((id, item));
+            }
+        }
 
-    let monkey_4 = Monkey {
-        items: VecDeque::from([50, 99, 67, 61, 86]),
-        business: 0,
-        mut_func: input4_mut, // Operation: new = old * old
-        test_div: 7,
-        true_monkey: 5,
-        false_monkey: 3
-    };
+        for (b, i) in stats.business.iter_mut().zip(&inspections) {
+            *b += i;
+        }
+        if stats.history {
+            stats.inspections.push(inspections);
+            stats.queues.push(queues.iter().map(|q| q.len()).collect());
+            stats.throws.push(throws);
+        }
+    }
 
-    let monkey_5 = Monkey {
-        items: VecDeque::from([61, 66, 72, 55, 64, 53, 72, 63]),
-        business: 0,
-        mut_func: input5_mut, // Operation: new = old + 4
-        test_div: 11,
-        true_monkey: 3,
-        false_monkey: 0
-    };
+    Ok(())
+}
 
-    let monkey_6 = Monkey {
-        items: VecDeque::from([59, 79, 63]),
-        business: 0,
-        mut_func: input6_mut, // Operation: new = old * 7
-        test_div: 17,
-        true_monkey: 2,
-        false_monkey: 7
-    };
+/*
+ * How many items each monkey inspects, found by following each item on
+ * its own. An item's path only depends on the monkey holding it and its
+ * worry level modulo the modulus at the start of a round, so once that
+ * repeats, the rounds between repeat until the end. The counts for all
+ * the cycles left are added at once
+ */
+fn extrapolate(monkeys: &[Monkey], config: &Config) -> Result<Vec<u64>, String> {
+    let modulus = modulus(monkeys, config)?
+        .ok_or("Extrapolating needs a product or lcm modulus")?;
+
+    let mut business = vec![0; monkeys.len()];
+    let items = monkeys.iter().enumerate()
+        .flat_map(|(i, m)| m.items.iter().map(move |w| (i, *w % modulus)));
+    for start in items {
+        // the state at the start of each round, and inspections before it
+        let mut seen = HashMap::new();
+        let mut counts = vec![vec![0u64; monkeys.len()]];
+        let (mut monkey, mut worry) = start;
+
+        let mut round = 0;
+        while round < config.rounds {
+            if let Some(first) = seen.insert((monkey, worry), round) {
+                let (left, cycle) = (config.rounds - round, round - first);
+                let (cycles, rest) = (left / cycle, left % cycle);
+                let (now, then, part) = (&counts[round], &counts[first], &counts[first + rest]);
+                for (i, b) in business.iter_mut().enumerate() {
+                    *b += now[i] + cycles as u64 * (now[i] - then[i]) + (part[i] - then[i]);
+                }
+                break;
+            }
 
-    let monkey_7 = Monkey {
-        items: VecDeque::from([55]),
-        business: 0,
-        mut_func: input7_mut, // Operation: new = old + 7
-        test_div: 3,
-        true_monkey: 2,
-        false_monkey: 1
-    };
+            // thrown to a monkey later in the round, the item is inspected again
+            let mut count = counts[round].clone();
+            loop {
+                let m = &monkeys[monkey];
+                count[monkey] += 1;
+                worry = m.operation.apply(&worry)
+                    .ok_or_else(|| format!("Worry level overflowed in round {}", round + 1))? % modulus;
+                let target = if worry % m.test_div == 0 { m.true_monkey } else { m.false_monkey };
+                let later = target > monkey;
+                monkey = target;
+                if !later {
+                    break;
+                }
+            }
+            counts.push(count);
+            round += 1;
+        }
+
+        if round == config.rounds {
+            for (b, c) in business.iter_mut().zip(&counts[round]) {
+                *b += c;
+            }
+        }
+    }
+
+    Ok(business)
+}
+
+// The two highest levels of monkey business multiplied together
+fn monkey_business(business: &[u64]) -> u128 {
+    let mut business = business.to_vec();
+    business.sort_by_key(|b| Reverse(*b));
 
-    vec![monkey_0, monkey_1, monkey_2, monkey_3, monkey_4, monkey_5, monkey_6, monkey_7]
+    business.iter().take(2).map(|b| *b as u128).product()
 }
 
-fn monkey_take_turn( monkeys: &mut [Monkey], i: usize) ->(VecDeque<i64>, VecDeque<i64>) {
-    let cur: &mut Monkey = monkeys.get_mut(i).unwrap();
+fn print_business(business: Result<Vec<u64>, String>) {
+    match business {
+        Ok(business) => {
+            for (i, b) in business.iter().enumerate() {
+                println!("Monkey {}: {}", i, b);
+            }
+            println!("Total monkey business: {}", monkey_business(&business));
+        },
+        Err(e) => println!("ERROR: {}", e),
+    }
+}
 
-    cur.take_turn()
+fn run(monkeys: &[Monkey], config: &Config) {
+    print_business(record(monkeys, config, false, false).map(|stats| stats.business));
 }
 
 fn main() {
-    //let mut monkeys = test_monkeys();
-    let mut monkeys = input_monkeys();
-    
-    for _ in 0..ROUNDS {
-        for i in 0..monkeys.len() {
-            //println!("Monkey {}:", i);
-            let (mut t, mut f) = monkey_take_turn(&mut monkeys, i);
-
-            // complete move of items
-            let cur = monkeys.get(i).unwrap();
-            let true_monkey = cur.true_monkey;
-            let false_monkey = cur.false_monkey;
-            if !t.is_empty() {
-                let m = monkeys.get_mut(true_monkey).unwrap();
-                m.items.append(&mut t);
+    // "simulate ROUNDS RELIEF MODULUS [file]" plays the rounds with relief
+    // "none" or a divisor, and modulus "none", "product", "lcm" or "bigint".
+    // "stats rounds|throws|items ROUNDS RELIEF MODULUS [file]" plays them
+    // and prints the inspections and queues, throws between monkeys or
+    // the path of every item each round as CSV. "extrapolate ROUNDS [file]"
+    // follows each item alone to count inspections over many more rounds
+    let args: Vec<String> = env::args().collect();
+    let mode = args.get(1).map(|a| a.as_str());
+    let file_arg = match mode {
+        Some("extrapolate") => 3,
+        Some("simulate") => 5,
+        Some("stats") => 6,
+        _ => 1
+    };
+    let filename = args.get(file_arg).map(|f| f.as_str()).unwrap_or(FILENAME);
+    let monkeys = match read_monkeys(filename) {
+        Ok(monkeys) => monkeys,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return;
+        }
+    };
+
+    let arg = |i: usize| args.get(i).map(|a| a.as_str())
+        .expect("Usage: simulate ROUNDS RELIEF MODULUS [file] | stats KIND ROUNDS RELIEF MODULUS [file] \
+            | extrapolate ROUNDS [file]");
+    match mode {
+        Some("extrapolate") => {
+            let config = number(arg(2)).map(|rounds| Config { rounds, ..PART_2 });
+            print_business(config.and_then(|config| extrapolate(&monkeys, &config)));
+            return;
+        },
+        Some("simulate") => {
+            match Config::from_args(arg(2), arg(3), arg(4)) {
+                Ok(config) => run(&monkeys, &config),
+                Err(e) => println!("ERROR: {}", e),
             }
-            if !f.is_empty() {
-                let m = monkeys.get_mut(false_monkey).unwrap();
-                m.items.append(&mut f);
+            return;
+        },
+        Some("stats") => {
+            let kind = arg(2);
+            let csv = Config::from_args(arg(3), arg(4), arg(5))
+                .and_then(|config| record(&monkeys, &config, kind != "items", kind == "items"))
+                .and_then(|stats| match kind {
+                    "rounds" => Ok(stats.rounds_csv()),
+                    "throws" => Ok(stats.throws_csv()),
+                    "items" => Ok(stats.items_csv().unwrap()),
+                    _ => Err(format!("Unknown stats '{}'", kind)),
+                });
+            match csv {
+                Ok(csv) => print!("{}", csv),
+                Err(e) => println!("ERROR: {}", e),
             }
+            return;
+        },
+        _ => {}
+    }
+
+    println!("Part 1:");
+    run(&monkeys, &PART_1);
+    println!("Part 2:");
+    run(&monkeys, &PART_2);
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn business(filename: &str, config: &Config) -> Result<Vec<u64>, String> {
+        record(&read_monkeys(filename).unwrap(), config, false, false).map(|stats| stats.business)
+    }
+
+    #[test]
+    fn test_parse() {
+        let monkeys = read_monkeys("./test").unwrap();
+        assert_eq!(monkeys.len(), 4);
+        assert_eq!(monkeys[2].items, vec![79, 60, 97]);
+        assert_eq!(monkeys[2].operation.apply(&5u64), Some(25));
+        assert_eq!(monkeys[1].operation.apply(&5u64), Some(11));
+        assert_eq!((monkeys[3].test_div, monkeys[3].true_monkey, monkeys[3].false_monkey), (17, 0, 1));
+
+        let (id, m) = Monkey::from_lines(&[
+            "Monkey 7:", "  Starting items:", "  Operation: new = 3 * old",
+            "  Test: divisible by 2", "    If true: throw to monkey 0", "    If false: throw to monkey 1"
+        ]).unwrap();
+        assert_eq!((id, m.items.len(), m.operation.apply(&4u64)), (7, 0, Some(12)));
+
+        assert_eq!(Operation::from_str("old - 3"), Err("Unknown operator '-'".to_string()));
+        assert_eq!(Operation::from_str("old * x"), Err("Bad operand 'x'".to_string()));
+        assert_eq!(Monkey::from_lines(&["Monkey 0:", "  Operation: new = old"]).err(),
+            Some("Expected 'Starting items:' but found 'Operation: new = old'".to_string()));
+    }
+
+    #[test]
+    fn test_business() {
+        assert_eq!(business("./test", &PART_1), Ok(vec![101, 95, 7, 105]));
+        assert_eq!(business("./test", &PART_2), Ok(vec![52166, 47830, 1938, 52013]));
+        assert_eq!(business("./input", &PART_1), Ok(vec![292, 296, 55, 60, 58, 258, 6, 298]));
+        assert_eq!(business("./input", &PART_2),
+            Ok(vec![145312, 76000, 69327, 69344, 118646, 75990, 13329, 145314]));
+
+        let config = Config { modulus: Modulus::Product, ..PART_2 };
+        assert_eq!(business("./test", &config), Ok(vec![52166, 47830, 1938, 52013]));
+
+        let config = Config { modulus: Modulus::None, ..PART_2 };
+        assert_eq!(business("./input", &config), Err("Worry level overflowed in round 6".to_string()));
+        let config = Config { relief: Relief::DivideBy(3), ..PART_2 };
+        assert_eq!(business("./input", &config), Err("Relief can't be used with a modulus".to_string()));
+    }
+
+    #[test]
+    fn test_against_bigint() {
+        for filename in ["./test", "./input"] {
+            for rounds in [1, 5, 20] {
+                let bigint = Config { rounds, relief: Relief::None, modulus: Modulus::BigInt };
+                let expected = business(filename, &bigint).unwrap();
+                for modulus in [Modulus::Product, Modulus::Lcm] {
+                    assert_eq!(business(filename, &Config { modulus, ..bigint }), Ok(expected.clone()));
+                }
+            }
+
+            let bigint = Config { modulus: Modulus::BigInt, ..PART_1 };
+            assert_eq!(business(filename, &PART_1), business(filename, &bigint));
         }
     }
 
-    // TODO: find 2 highest levels of monkey business and multiply them
-    let mut max_1 = 0;
-    let mut max_2 = 0;
-    for (i, m) in monkeys.iter().enumerate() {
-        println!("Monkey {}: {}", i, m.business);
+    #[test]
+    fn test_stats() {
+        let monkeys = read_monkeys("./test").unwrap();
+        let stats = record(&monkeys, &PART_1, true, true).unwrap();
+        assert_eq!(monkey_business(&stats.business), 10605);
+        assert_eq!(monkey_business(&record(&monkeys, &PART_2, false, false).unwrap().business), 2713310158);
+
+        // after round 1 in the puzzle text
+        assert_eq!(stats.inspections[0], vec![2, 4, 3, 5]);
+        assert_eq!(stats.queues[0], vec![4, 6, 0, 0]);
+        assert_eq!(stats.throws[0], vec![
+            0, 0, 0, 2,
+            4, 0, 0, 0,
+            0, 1, 0, 2,
+            0, 5, 0, 0
+        ]);
+
+        // items are neither lost nor made
+        for (inspections, throws) in stats.inspections.iter().zip(&stats.throws) {
+            assert_eq!(inspections.iter().sum::<u64>(), throws.iter().sum::<u64>());
+        }
+        for queues in &stats.queues {
+            assert_eq!(queues.iter().sum::<usize>(), 10);
+        }
+
+        // the item starting as 79 with monkey 0 goes to monkey 3, then 1
+        // in the same round, then 2 in the next
+        let paths = stats.paths.as_ref().unwrap();
+        assert_eq!(paths.len(), 10);
+        assert_eq!(&paths[0][..3], &[(1, 0, 3), (1, 3, 1), (2, 1, 2)]);
+        let total: usize = paths.iter().map(|p| p.len()).sum();
+        assert_eq!(total as u64, stats.business.iter().sum::<u64>());
+
+        let items = stats.items_csv().unwrap();
+        assert!(items.starts_with("item,round,from,to\n0,1,0,3\n0,1,3,1\n"));
+        assert!(stats.rounds_csv().starts_with("round,monkey,inspections,queue\n1,0,2,4\n1,1,4,6\n"));
+        assert!(stats.throws_csv().starts_with("round,from,to,items\n1,0,3,2\n1,1,0,4\n1,2,1,1\n"));
+
+        // without asking, only the totals are kept
+        let totals = record(&monkeys, &PART_1, false, false).unwrap();
+        assert_eq!(totals.business, stats.business);
+        assert!(totals.inspections.is_empty() && totals.queues.is_empty() && totals.throws.is_empty());
+        assert!(totals.items_csv().is_none());
+    }
 
-        if m.business > max_1 {
-            max_2 = max_1;
-            max_1 = m.business;
-        } else if m.business > max_2 {
-            max_2 = m.business;
+    #[test]
+    fn test_extrapolate() {
+        for filename in ["./test", "./input"] {
+            let monkeys = read_monkeys(filename).unwrap();
+            for rounds in [0, 1, 7, 20, 1000, 10000] {
+                let config = Config { rounds, ..PART_2 };
+                assert_eq!(extrapolate(&monkeys, &config), business(filename, &config));
+            }
         }
+
+        let monkeys = read_monkeys("./input").unwrap();
+        let config = Config { rounds: 1_000_000_000, ..PART_2 };
+        let business = extrapolate(&monkeys, &config).unwrap();
+        let items = monkeys.iter().map(|m| m.items.len() as u64).sum::<u64>();
+        assert!(business.iter().sum::<u64>() >= items * config.rounds as u64);
+
+        assert_eq!(extrapolate(&monkeys, &PART_1),
+            Err("Extrapolating needs a product or lcm modulus".to_string()));
     }
 
-    println!("Total monkey business: {} * {} = {}", max_1, max_2, max_1 * max_2);
+    #[test]
+    fn test_biguint() {
+        let big = BigUint::from_u64(u64::MAX);
+        let square = big.mul(&big).unwrap();
+        assert_eq!(square.to_string(), "340282366920938463426481119284349108225");
+        assert_eq!(square.add(&BigUint::from_u64(1)).unwrap().rem(u64::MAX), 1);
+        assert_eq!(square.div(u64::MAX), big);
+        assert_eq!(BigUint::from_u64(0).to_string(), "0");
+        assert_eq!(BigUint::from_u64(1_000_000_007).to_string(), "1000000007");
+    }
 }
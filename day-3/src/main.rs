@@ -1,8 +1,9 @@
-
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 
 const FILENAME: &str = "./input";
+const COMPARTMENTS: usize = 2;
+const GROUP_SIZE: usize = 3;
 
 fn letter_to_priority(c: char) -> u32 {
     let mut pri = c as u32;
@@ -21,81 +22,196 @@ fn letter_to_priority(c: char) -> u32 {
     pri
 }
 
-fn part_1() {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+fn priority_to_letter(pri: u32) -> char {
+    if pri <= 26 {
+        (b'a' + (pri - 1) as u8) as char
+    } else {
+        (b'A' + (pri - 27) as u8) as char
+    }
+}
 
-    // Read file line by line
-    let mut all_dups = Vec::new();
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
+/*
+ * Each item gets one bit in a 52-bit mask, bit 0 being 'a' (priority 1)
+ * and bit 51 being 'Z' (priority 52)
+ */
+fn item_to_bit(c: char) -> Result<u64, String> {
+    if !c.is_ascii_alphabetic() {
+        return Err(format!("Invalid item '{}'", c));
+    }
+
+    Ok(1 << (letter_to_priority(c) - 1))
+}
 
-        // split string in 2
-        let comp_len = line_str.len() / 2;
-        let comp_1 = &line_str[..comp_len];
-        let comp_2 = &line_str[comp_len..];
-
-        // find common characters
-        let mut duplicates = Vec::new();
-        for c in comp_1.chars() {
-            if comp_2.contains(c) && !duplicates.contains(&c) {
-                duplicates.push(c);
-                all_dups.push(c)
-            }
+fn mask_to_items(mask: u64) -> Vec<char> {
+    let mut items = Vec::new();
+    for bit in 0..52 {
+        if mask & (1 << bit) != 0 {
+            items.push(priority_to_letter(bit + 1));
         }
     }
 
+    items
+}
 
-    // create sum
-    let mut sum = 0;
-    for d in all_dups.iter() {
-        let pri = letter_to_priority(*d);
-        sum += pri;
+fn mask_priority(mask: u64) -> u32 {
+    mask_to_items(mask).iter().map(|c| letter_to_priority(*c)).sum()
+}
+
+#[derive(Debug,Clone)]
+struct Rucksack {
+    compartments: Vec<u64>
+}
+
+impl Rucksack {
+
+    fn from_line(line: &str, num_compartments: usize) -> Result<Rucksack, String> {
+        if num_compartments == 0 {
+            return Err("Rucksack needs at least one compartment".to_string());
+        }
+        if !line.len().is_multiple_of(num_compartments) {
+            return Err(format!(
+                "Line '{}' of length {} does not split into {} compartments",
+                line, line.len(), num_compartments));
+        }
+
+        let comp_len = line.len() / num_compartments;
+        let mut compartments = vec![0; num_compartments];
+        for (i, c) in line.chars().enumerate() {
+            let bit = item_to_bit(c)
+                .map_err(|e| format!("{} in line '{}'", e, line))?;
+            compartments[i / comp_len] |= bit;
+        }
+
+        Ok(Rucksack { compartments })
     }
 
-    // Print the answer to the first part
-    println!("First Answer: {:?}", sum);
+    fn contents(&self) -> u64 {
+        self.compartments.iter().fold(0, |acc, c| acc | c)
+    }
+
+    // Items which appear in every compartment
+    fn shared_items(&self) -> u64 {
+        self.compartments.iter().fold(u64::MAX, |acc, c| acc & c)
+    }
 }
 
+// Items which appear in every rucksack of the group
+fn group_badges(group: &[Rucksack]) -> u64 {
+    group.iter().fold(u64::MAX, |acc, r| acc & r.contents())
+}
 
-fn part_2() {
+fn read_rucksacks(
+        filename: &str,
+        num_compartments: usize) -> Vec<Result<Rucksack, String>> {
     // Open the file
-    let file = File::open(FILENAME).unwrap();
+    let file = File::open(filename).unwrap();
     let reader = BufReader::new(file);
 
     // Read file line by line
-    let mut sum = 0;
-    let mut group = Vec::new();
-    for line in reader.lines() {
+    let mut rucksacks = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
         let line_str = line.expect("Couldn't read line as string?");
 
-        group.push(line_str);
-        if group.len() < 3 {
-            continue;
-        }
+        rucksacks.push(Rucksack::from_line(&line_str, num_compartments)
+            .map_err(|e| format!("Error on line {}: {}", i + 1, e)));
+    }
 
-        let elf_3 = group.pop().unwrap();
-        let elf_2 = group.pop().unwrap();
-        let elf_1 = group.pop().unwrap();
+    rucksacks
+}
 
-        // find common characters
-        for c in elf_1.chars() {
-            if elf_2.contains(c) && elf_3.contains(c) {
-                sum += letter_to_priority(c);
-                break;
-            }
-        }
+// Bad lines are left out, as each rucksack stands alone
+fn part_1(rucksacks: &[Result<Rucksack, String>]) -> u32 {
+    rucksacks.iter()
+        .flatten()
+        .map(|r| mask_priority(r.shared_items()))
+        .sum()
+}
+
+// Groups are taken from consecutive lines of the file, so a bad line
+// spoils its whole group rather than being skipped
+fn part_2(
+        rucksacks: &[Result<Rucksack, String>],
+        group_size: usize) -> Result<u32, String> {
+    if group_size == 0 || !rucksacks.len().is_multiple_of(group_size) {
+        return Err(format!("{} rucksacks do not split into groups of {}",
+            rucksacks.len(), group_size));
     }
 
-    // Print the answer to the second part
-    println!("Second Answer: {:?}", sum);
+    let mut sum = 0;
+    for (i, group) in rucksacks.chunks(group_size).enumerate() {
+        let group = group.iter().cloned().collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Can't find badge for group {}: {}", i + 1, e))?;
+        sum += mask_priority(group_badges(&group));
+    }
+
+    Ok(sum)
 }
 
 fn main() {
     println!("Advent of Code, Day 3");
 
-    part_1();
-    part_2();
+    let rucksacks = read_rucksacks(FILENAME, COMPARTMENTS);
+    for e in rucksacks.iter().filter_map(|r| r.as_ref().err()) {
+        println!("{}", e);
+    }
+
+    // Print the answer to the first part
+    println!("First Answer: {:?}", part_1(&rucksacks));
+
+    // Print the answer to the second part
+    match part_2(&rucksacks, GROUP_SIZE) {
+        Ok(sum) => println!("Second Answer: {:?}", sum),
+        Err(e) => println!("Error: {}", e)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn get_test_rucksacks() -> Vec<Rucksack> {
+        read_rucksacks("./test", COMPARTMENTS).into_iter()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_shared_items() {
+        let rucksacks = get_test_rucksacks();
+        let shared: Vec<Vec<char>> = rucksacks.iter()
+            .map(|r| mask_to_items(r.shared_items()))
+            .collect();
+
+        assert_eq!(shared, vec![
+            vec!['p'], vec!['L'], vec!['P'], vec!['v'], vec!['t'], vec!['s']]);
+        assert_eq!(part_1(&read_rucksacks("./test", COMPARTMENTS)), 157);
+    }
+
+    #[test]
+    fn test_group_badges() {
+        let mut rucksacks = read_rucksacks("./test", COMPARTMENTS);
+        assert_eq!(part_2(&rucksacks, GROUP_SIZE), Ok(70));
+        assert!(part_2(&rucksacks, 4).is_err());
+
+        // A bad line fails its own group instead of shifting the rest
+        rucksacks[4] = Rucksack::from_line("abc", COMPARTMENTS);
+        assert!(part_2(&rucksacks, GROUP_SIZE).unwrap_err()
+            .starts_with("Can't find badge for group 2"));
+
+        // Part 1 just leaves it out, losing the 20 for its 't'
+        assert_eq!(part_1(&rucksacks), 157 - 20);
+    }
+
+    #[test]
+    fn test_many_compartments() {
+        let r = Rucksack::from_line("abcaxcayc", 3).unwrap();
+        assert_eq!(mask_to_items(r.shared_items()), vec!['a', 'c']);
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert!(Rucksack::from_line("abc", 2).is_err());
+        assert!(Rucksack::from_line("ab1c", 2).is_err());
+    }
+}
@@ -1,94 +1,534 @@
-
+use std::cmp::{max, min};
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
 
 const FILENAME: &str = "./input";
 
-fn range_from_str(s: &str) -> Result<Vec<u32>, String> {
-    let range: Vec<&str> = s.split('-').collect();
+/*
+ * An inclusive range of section IDs
+ */
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord)]
+struct Interval {
+    start: u32,
+    end: u32
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl Interval {
+
+    fn new(start: u32, end: u32) -> Result<Interval, String> {
+        if start > end {
+            Err(format!("Error: Range '{}-{}' ends before it starts", start, end))
+        } else {
+            Ok(Interval { start, end })
+        }
+    }
+
+    // In u64, as 0-4294967295 holds one more section than fits in a u32
+    fn len(&self) -> u64 {
+        u64::from(self.end) - u64::from(self.start) + 1
+    }
+
+    fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    #[allow(dead_code)]
+    fn contains_section(&self, section: u32) -> bool {
+        self.start <= section && section <= self.end
+    }
+
+    fn overlaps(&self, other: &Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    // Overlapping, or touching end to start with no gap in between
+    fn adjacent_or_overlaps(&self, other: &Interval) -> bool {
+        self.start <= other.end.saturating_add(1)
+            && other.start <= self.end.saturating_add(1)
+    }
 
-    if range.len() != 2 {
-        Err(format!("Error: Range '{}' did not split into 2 ranges", s))
+    #[allow(dead_code)]
+    fn intersection(&self, other: &Interval) -> Option<Interval> {
+        if self.overlaps(other) {
+            Some(Interval {
+                start: max(self.start, other.start),
+                end: min(self.end, other.end)
+            })
+        } else {
+            None
+        }
+    }
 
-    } else {
+    #[allow(dead_code)]
+    fn union(&self, other: &Interval) -> IntervalSet {
+        let mut set = IntervalSet::new();
+        set.insert(*self);
+        set.insert(*other);
 
-        let lower: u32 = range[0].parse().expect("Failed to parse range to int");
-        let upper: u32 = range[1].parse().expect("Failed to parse range to int");
+        set
+    }
 
-        Ok(vec![lower, upper])
+    fn difference(&self, other: &Interval) -> IntervalSet {
+        let mut set = IntervalSet::new();
+        if !self.overlaps(other) {
+            set.insert(*self);
+            return set;
+        }
+
+        if self.start < other.start {
+            set.insert(Interval { start: self.start, end: other.start - 1 });
+        }
+        if other.end < self.end {
+            set.insert(Interval { start: other.end + 1, end: self.end });
+        }
+
+        set
     }
 }
 
-fn part_1() -> i32 {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+/*
+ * A set of sections stored as sorted, disjoint intervals. Adjacent
+ * intervals are merged, so 2-4 and 5-6 become 2-6
+ */
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+struct IntervalSet {
+    intervals: Vec<Interval>
+}
 
-    // Read file line by line
-    let mut answer = 0;
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
+impl fmt::Display for IntervalSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strs: Vec<String> = self.intervals.iter()
+            .map(|i| i.to_string())
+            .collect();
+        write!(f, "{{{}}}", strs.join(", "))
+    }
+}
 
-        // split string in 2
-        let ranges: Vec<&str> = line_str.split(',').collect();
-        if ranges.len() != 2 {
-            println!("Error: Line '{}' did not split into 2 ranges", line_str);
-            continue;
+impl IntervalSet {
+
+    fn new() -> IntervalSet {
+        IntervalSet { intervals: Vec::new() }
+    }
+
+    fn insert(&mut self, interval: Interval) {
+        let mut merged = interval;
+        let mut intervals = Vec::new();
+        for i in self.intervals.iter() {
+            if i.adjacent_or_overlaps(&merged) {
+                merged = Interval {
+                    start: min(i.start, merged.start),
+                    end: max(i.end, merged.end)
+                };
+            } else {
+                intervals.push(*i);
+            }
         }
 
-        let range_1 = range_from_str(ranges[0]).expect("Failed to parse string");
-        let range_2 = range_from_str(ranges[1]).expect("Failed to parse string");
+        let pos = intervals.partition_point(|i| i.start < merged.start);
+        intervals.insert(pos, merged);
+        self.intervals = intervals;
+    }
 
-        if range_1[0] >= range_2[0] && range_1[1] <= range_2[1]
-                || range_2[0] >= range_1[0] && range_2[1] <= range_1[1] {
-            answer += 1;
+    fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    // Number of sections in the set
+    fn len(&self) -> u64 {
+        self.intervals.iter().map(|i| i.len()).sum()
+    }
+
+    #[allow(dead_code)]
+    fn contains_section(&self, section: u32) -> bool {
+        self.intervals.iter().any(|i| i.contains_section(section))
+    }
+
+    // Sections in bounds which are not in the set
+    fn gaps(&self, bounds: &Interval) -> IntervalSet {
+        let mut set = IntervalSet::new();
+        set.insert(*bounds);
+        for i in self.intervals.iter() {
+            set = set.difference(i);
         }
+
+        set
     }
 
-    // Print the answer to the first part
-    answer
+    fn difference(&self, other: &Interval) -> IntervalSet {
+        let mut set = IntervalSet::new();
+        for i in self.intervals.iter() {
+            for d in i.difference(other).intervals {
+                set.insert(d);
+            }
+        }
+
+        set
+    }
 }
 
+fn range_from_str(s: &str) -> Result<Interval, String> {
+    let (lower, upper) = s.split_once('-')
+        .ok_or(format!("Error: Range '{}' did not split into 2 ranges", s))?;
 
-fn part_2() -> i32 {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+    let lower: u32 = lower.parse()
+        .map_err(|_| format!("Error: Failed to parse '{}' to int", lower))?;
+    let upper: u32 = upper.parse()
+        .map_err(|_| format!("Error: Failed to parse '{}' to int", upper))?;
+
+    Interval::new(lower, upper)
+}
+
+fn group_from_line(line: &str) -> Result<Vec<Interval>, String> {
+    let mut group = Vec::new();
+    for range in line.split(',') {
+        group.push(range_from_str(range)
+            .map_err(|e| format!("{} in line '{}'", e, line))?);
+    }
 
-    // Read file line by line
-    let mut answer = 0;
-    for line in reader.lines() {
+    Ok(group)
+}
+
+// Every group that parses, with the number of the line it is on
+fn read_numbered_groups<R: BufRead>(reader: R) -> Vec<(usize, Vec<Interval>)> {
+    // Read line by line
+    let mut groups = Vec::new();
+    for (line_num, line) in reader.lines().enumerate() {
         let line_str = line.expect("Couldn't read line as string?");
 
-        // split string in 2
-        let ranges: Vec<&str> = line_str.split(',').collect();
-        if ranges.len() != 2 {
-            println!("Error: Line '{}' did not split into 2 ranges", line_str);
-            continue;
+        match group_from_line(&line_str) {
+            Ok(group) => groups.push((line_num + 1, group)),
+            Err(e) => println!("{}", e)
         }
+    }
+
+    groups
+}
+
+fn load_numbered_groups(filename: &str) -> Vec<(usize, Vec<Interval>)> {
+    // Open the file
+    let file = File::open(filename).unwrap();
+    read_numbered_groups(BufReader::new(file))
+}
 
-        let range_1 = range_from_str(ranges[0]).expect("Failed to parse string");
-        let range_2 = range_from_str(ranges[1]).expect("Failed to parse string");
+fn read_groups(filename: &str) -> Vec<Vec<Interval>> {
+    load_numbered_groups(filename).into_iter()
+        .map(|(_, group)| group)
+        .collect()
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Relation {
+    Equal,
+    Contains,
+    ContainedBy,
+    Overlaps,
+    Disjoint
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Relation::Equal => "=",
+            Relation::Contains => "C",
+            Relation::ContainedBy => "c",
+            Relation::Overlaps => "o",
+            Relation::Disjoint => "."
+        })
+    }
+}
 
-        if (range_1[0] >= range_2[0] && range_1[0] <= range_2[1])
-                || (range_2[0] >= range_1[0] && range_2[0] <= range_1[1]) {
-            answer += 1;
+impl Relation {
+
+    fn between(a: &Interval, b: &Interval) -> Relation {
+        if a == b {
+            Relation::Equal
+        } else if a.contains(b) {
+            Relation::Contains
+        } else if b.contains(a) {
+            Relation::ContainedBy
+        } else if a.overlaps(b) {
+            Relation::Overlaps
+        } else {
+            Relation::Disjoint
         }
     }
+}
 
-    // Print the answer to the second part
-    answer
+/*
+ * Relation of every elf in a group to every other elf, read as
+ * "row elf <relation> column elf"
+ */
+fn relation_matrix(group: &[Interval]) -> Vec<Vec<Relation>> {
+    group.iter()
+        .map(|a| group.iter().map(|b| Relation::between(a, b)).collect())
+        .collect()
+}
+
+/*
+ * Elves whose sections are all covered by the rest of the group. Note
+ * that two elves with equal assignments are each redundant on their own,
+ * so only one of them can actually be dropped
+ */
+fn redundant_elves(group: &[Interval]) -> Vec<usize> {
+    let mut redundant = Vec::new();
+    for (i, elf) in group.iter().enumerate() {
+        let mut remaining = IntervalSet::new();
+        remaining.insert(*elf);
+        for (j, other) in group.iter().enumerate() {
+            if i != j {
+                remaining = remaining.difference(other);
+            }
+        }
+
+        if remaining.is_empty() {
+            redundant.push(i);
+        }
+    }
+
+    redundant
+}
+
+fn print_report(groups: &[(usize, Vec<Interval>)]) {
+    for (line_num, group) in groups {
+        let strs: Vec<String> = group.iter().map(|i| i.to_string()).collect();
+        println!("Line {}: {}", line_num, strs.join(","));
+
+        let matrix = relation_matrix(group);
+        let header: Vec<String> = (0..group.len())
+            .map(|i| format!("{:>3}", i))
+            .collect();
+        println!("     {}", header.join(""));
+        for (i, row) in matrix.iter().enumerate() {
+            let cells: Vec<String> = row.iter().enumerate()
+                .map(|(j, r)| if i == j {
+                    format!("{:>3}", '-')
+                } else {
+                    format!("{:>3}", r)
+                })
+                .collect();
+            println!("  {:>3}{}", i, cells.join(""));
+        }
+
+        let redundant = redundant_elves(group);
+        if redundant.is_empty() {
+            println!("  No redundant elves");
+        } else {
+            println!("  Redundant elves: {:?}", redundant);
+        }
+    }
+}
+
+// Union of every assignment in the file
+fn covered_sections(groups: &[Vec<Interval>]) -> IntervalSet {
+    let mut set = IntervalSet::new();
+    for i in groups.iter().flatten() {
+        set.insert(*i);
+    }
+
+    set
+}
+
+// Sections between the lowest and highest assigned section that no elf has
+fn uncovered_sections(groups: &[Vec<Interval>]) -> IntervalSet {
+    let covered = covered_sections(groups);
+    match (covered.intervals.first(), covered.intervals.last()) {
+        (Some(first), Some(last)) => covered.gaps(&Interval {
+            start: first.start,
+            end: last.end
+        }),
+        _ => IntervalSet::new()
+    }
+}
+
+/*
+ * Sweep over the start and end points of every assignment, returning the
+ * most elves assigned to any one section and the sections where that occurs
+ */
+fn max_coverage(groups: &[Vec<Interval>]) -> (u32, IntervalSet) {
+    let mut events = Vec::new();
+    for i in groups.iter().flatten() {
+        // Ends are pushed one past the range, which may be past u32::MAX
+        events.push((u64::from(i.start), 1));
+        events.push((u64::from(i.end) + 1, -1));
+    }
+    events.sort();
+
+    let mut best = 0;
+    let mut best_sections = IntervalSet::new();
+    let mut count = 0;
+    for (idx, (section, delta)) in events.iter().enumerate() {
+        count += delta;
+
+        // Only check the count once all events at this section are applied
+        let next_section = match events.get(idx + 1) {
+            Some((s, _)) if s == section => continue,
+            Some((s, _)) => *s,
+            None => break
+        };
+
+        // Both fit in a u32, as there is a later event past this section
+        let span = Interval { start: *section as u32, end: (next_section - 1) as u32 };
+        if count > best {
+            best = count;
+            best_sections = IntervalSet::new();
+            best_sections.insert(span);
+        } else if count == best && best > 0 {
+            best_sections.insert(span);
+        }
+    }
+
+    (best as u32, best_sections)
+}
+
+// Whether any two different elves in the group have a matching relation
+fn any_pair(group: &[Interval], pred: fn(Relation) -> bool) -> bool {
+    relation_matrix(group).iter().enumerate()
+        .any(|(i, row)| row.iter().enumerate()
+            .any(|(j, r)| i != j && pred(*r)))
+}
+
+// Groups where at least one elf's assignment is inside another's
+fn part_1(groups: &[Vec<Interval>]) -> i32 {
+    groups.iter()
+        .filter(|g| any_pair(g, |r| matches!(r,
+            Relation::Equal | Relation::Contains | Relation::ContainedBy)))
+        .count() as i32
+}
+
+
+// Groups where at least two elves' assignments overlap
+fn part_2(groups: &[Vec<Interval>]) -> i32 {
+    groups.iter()
+        .filter(|g| any_pair(g, |r| r != Relation::Disjoint))
+        .count() as i32
 }
 
 fn main() {
     println!("Advent of Code, Day 4");
 
-    let answer = part_1();
+    // "report [file]" prints the relation matrix for every line
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(|a| a.as_str()) == Some("report") {
+        let filename = args.get(2).map(|f| f.as_str()).unwrap_or(FILENAME);
+        print_report(&load_numbered_groups(filename));
+        return;
+    }
+
+    let groups = read_groups(FILENAME);
+
+    let answer = part_1(&groups);
     println!("First Answer: {:?}", answer);
     assert_eq!(answer, 494);
 
-    let answer = part_2();
+    let answer = part_2(&groups);
     println!("Second Answer: {:?}", answer);
     assert_eq!(answer, 833);
+
+    let uncovered = uncovered_sections(&groups);
+    println!("Sections covered by no elf: {} ({} sections)",
+        uncovered, uncovered.len());
+
+    let (count, sections) = max_coverage(&groups);
+    println!("Max simultaneous coverage: {} elves on sections {}",
+        count, sections);
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn iv(start: u32, end: u32) -> Interval {
+        Interval::new(start, end).unwrap()
+    }
+
+    #[test]
+    fn test_interval_ops() {
+        assert!(iv(2, 8).contains(&iv(3, 7)));
+        assert!(!iv(3, 7).contains(&iv(2, 8)));
+        assert!(iv(5, 7).overlaps(&iv(7, 9)));
+        assert!(!iv(2, 4).overlaps(&iv(6, 8)));
+
+        assert_eq!(iv(5, 7).intersection(&iv(7, 9)), Some(iv(7, 7)));
+        assert_eq!(iv(2, 4).intersection(&iv(6, 8)), None);
+
+        assert_eq!(iv(2, 4).union(&iv(5, 6)).intervals, vec![iv(2, 6)]);
+        assert_eq!(iv(2, 4).union(&iv(6, 8)).intervals, vec![iv(2, 4), iv(6, 8)]);
+
+        assert_eq!(iv(2, 8).difference(&iv(4, 5)).intervals,
+            vec![iv(2, 3), iv(6, 8)]);
+        assert!(iv(4, 5).difference(&iv(2, 8)).is_empty());
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let mut set = IntervalSet::new();
+        set.insert(iv(10, 12));
+        set.insert(iv(1, 2));
+        set.insert(iv(5, 6));
+        set.insert(iv(3, 4));
+
+        assert_eq!(set.intervals, vec![iv(1, 6), iv(10, 12)]);
+        assert_eq!(set.len(), 9);
+        assert!(set.contains_section(11));
+        assert!(!set.contains_section(8));
+        assert_eq!(set.gaps(&iv(0, 13)).intervals,
+            vec![iv(0, 0), iv(7, 9), iv(13, 13)]);
+    }
+
+    #[test]
+    fn test_file_queries() {
+        let groups = read_groups("./test");
+        assert_eq!(part_1(&groups), 2);
+        assert_eq!(part_2(&groups), 4);
+
+        assert!(uncovered_sections(&groups).is_empty());
+
+        let (count, sections) = max_coverage(&groups);
+        assert_eq!(count, 8);
+        assert_eq!(sections.intervals, vec![iv(6, 6)]);
+
+        // ranges may run right up to the last section
+        let groups = vec![group_from_line("0-4294967295,5-6").unwrap()];
+        assert_eq!(max_coverage(&groups), (2, IntervalSet { intervals: vec![iv(5, 6)] }));
+        assert_eq!(covered_sections(&groups).len(), 1 << 32);
+    }
+
+    #[test]
+    fn test_bad_ranges() {
+        assert!(range_from_str("5-2").is_err());
+        assert!(range_from_str("5").is_err());
+        assert!(range_from_str("a-2").is_err());
+        assert!(group_from_line("1-2,3-4,x-6").is_err());
+
+        // groups keep the line they came from when earlier lines are bad
+        let groups = read_numbered_groups("2-x,3-4\n1-5,2-3\n7-6,1-1\n4-4,4-5\n".as_bytes());
+        assert_eq!(groups, vec![(2, vec![iv(1, 5), iv(2, 3)]), (4, vec![iv(4, 4), iv(4, 5)])]);
+    }
+
+    #[test]
+    fn test_groups() {
+        let group = group_from_line("2-8,3-7,1-4,3-7,10-12").unwrap();
+        let matrix = relation_matrix(&group);
+
+        assert_eq!(matrix[0][1], Relation::Contains);
+        assert_eq!(matrix[1][0], Relation::ContainedBy);
+        assert_eq!(matrix[1][2], Relation::Overlaps);
+        assert_eq!(matrix[1][3], Relation::Equal);
+        assert_eq!(matrix[0][4], Relation::Disjoint);
+
+        assert_eq!(redundant_elves(&group), vec![1, 3]);
+
+        // 2-5 is only covered by the union of the other two
+        let group = group_from_line("1-3,2-5,4-6").unwrap();
+        assert_eq!(redundant_elves(&group), vec![1]);
+    }
+}
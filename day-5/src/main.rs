@@ -1,7 +1,11 @@
-use std::fs::File;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{prelude::*, BufReader};
 
 const FILENAME: &str = "./input";
+const MAX_PLAN_STATES: usize = 1_000_000;
 
 fn crates_from_str(l: &str) -> Vec::<char> {
     let mut rval = Vec::<char>::new();
@@ -12,11 +16,11 @@ fn crates_from_str(l: &str) -> Vec::<char> {
     // with input of the form:
     //     0123456
     //     -------
-    //         [D]    
-    //     [N] [C]    
+    //         [D]
+    //     [N] [C]
     //
     // We can start at index 1, push the value onto the return vector,
-    // increment the index by 4, and continue until the index exceeds the 
+    // increment the index by 4, and continue until the index exceeds the
     // bounds of the string. Spaces will signify nothing in the stack at
     // that index.
     while i < char_vec.len() {
@@ -27,49 +31,127 @@ fn crates_from_str(l: &str) -> Vec::<char> {
     rval
 }
 
-fn moves_from_str(l: &str) -> (usize, usize, usize) {
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct Move {
+    num: usize,
+    src: usize,
+    dst: usize
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.num, self.src, self.dst)
+    }
+}
+
+fn moves_from_str(l: &str) -> Result<Move, String> {
     let l_vec: Vec::<&str> = l.split(' ').collect();
 
-    if l_vec.len() != 6 {
-        return( 0, 0, 0);
+    if l_vec.len() != 6 || l_vec[0] != "move" || l_vec[2] != "from" || l_vec[4] != "to" {
+        return Err(format!("Error: '{}' is not a move", l));
     }
 
-    let num = l_vec[1].parse().expect("Could not parse number of crates to move");
-    let src = l_vec[3].parse().expect("Could not parse source of crates");
-    let dst = l_vec[5].parse().expect("Could not parse dest. of crates");
+    let parse = |s: &str| s.parse::<usize>()
+        .map_err(|_| format!("Error: Could not parse '{}' in move '{}'", s, l));
 
-    (num, src, dst)
+    Ok(Move {
+        num: parse(l_vec[1])?,
+        src: parse(l_vec[3])?,
+        dst: parse(l_vec[5])?
+    })
 }
 
-fn part_1() {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+/*
+ * A crane decides what order the crates of one move land in. Both sides
+ * are bottom to top: the crates as they sat on the source stack, and the
+ * order they end up in on the destination stack
+ */
+trait Crane {
+    fn name(&self) -> String;
+    fn arrange(&self, lifted: &[char]) -> Vec<char>;
+}
 
-    // init state for answer
-    let mut answer = String::new();
-    let mut building_stacks = true;
-    let mut stacks = Vec::<Vec::<char>>::new();
+// Moves one crate at a time, reversing their order
+struct CrateMover9000;
 
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
 
-        if building_stacks {
-            // check for end of initial state
-            if line_str.starts_with(" 1 ") {
-                building_stacks = false;
+    fn arrange(&self, lifted: &[char]) -> Vec<char> {
+        lifted.iter().rev().copied().collect()
+    }
+}
 
-                // print starting point
-                for (i, stack) in stacks.iter().enumerate() {
-                    println!("{}: {:?}", i, stack);
-                }
+// Moves all crates at once, preserving their order
+struct CrateMover9001;
 
-                continue;
-            }
-            
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn arrange(&self, lifted: &[char]) -> Vec<char> {
+        lifted.to_vec()
+    }
+}
+
+// Moves up to max_lift crates at a time, preserving the order of each lift
+struct MaxLiftCrane {
+    max_lift: usize
+}
+
+impl Crane for MaxLiftCrane {
+    fn name(&self) -> String {
+        format!("Max lift {} crane", self.max_lift)
+    }
+
+    fn arrange(&self, lifted: &[char]) -> Vec<char> {
+        // the top lift comes off first and ends up on the bottom
+        lifted.rchunks(self.max_lift.max(1)).flatten().copied().collect()
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+struct CrateYard {
+    stacks: Vec<Vec<char>>
+}
+
+/*
+ * Render the yard in the same drawing format as the puzzle input, padded
+ * to the full width on every line and ending with the numbered footer
+ */
+impl fmt::Display for CrateYard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+
+        for level in (0..height).rev() {
+            let cells: Vec<String> = self.stacks.iter()
+                .map(|s| match s.get(level) {
+                    Some(c) => format!("[{}]", c),
+                    None => "   ".to_string()
+                })
+                .collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+
+        let footer: Vec<String> = (1..=self.stacks.len())
+            .map(|i| format!("{:^3}", i))
+            .collect();
+        write!(f, "{}", footer.join(" "))
+    }
+}
+
+impl CrateYard {
+
+    // Build the yard from the drawing lines, not including the numbered footer
+    fn from_lines(lines: &[String]) -> CrateYard {
+        let mut stacks = Vec::<Vec::<char>>::new();
+
+        for line in lines.iter() {
             // split line into crates at this layer
-            let crates = crates_from_str(&line_str);
+            let crates = crates_from_str(line);
 
             // add this layer of crates to stacks, inserting at "bottom"
             for (i, c) in crates.iter().enumerate() {
@@ -82,107 +164,569 @@ fn part_1() {
                     stacks[i].insert(0, *c);
                 }
             }
+        }
 
-        } else {
-            if !line_str.starts_with("move") {
-                continue;
+        CrateYard { stacks }
+    }
+
+    // Parse a full drawing, including the numbered footer
+    fn from_drawing(drawing: &str) -> Result<CrateYard, String> {
+        let mut lines: Vec<String> = drawing.lines()
+            .map(|l| l.to_string())
+            .collect();
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+
+        let footer = lines.pop()
+            .ok_or("Error: Drawing is empty".to_string())?;
+        for (i, num) in footer.split_whitespace().enumerate() {
+            if num.parse::<usize>() != Ok(i + 1) {
+                return Err(format!("Error: Expected stack number {} in footer '{}'",
+                    i + 1, footer));
             }
-            let (num, src, dst) = moves_from_str(&line_str);
+        }
+        let num_stacks = footer.split_whitespace().count();
+
+        for line in lines.iter() {
+            let chars: Vec<char> = line.chars().collect();
+            for (i, cell) in chars.chunks(4).enumerate() {
+                let valid = match cell {
+                    [' ', ' ', ' '] | [' ', ' ', ' ', ' '] => true,
+                    ['[', c, ']'] | ['[', c, ']', ' '] => c.is_ascii_alphabetic(),
+                    _ => false
+                };
+                if !valid || (i >= num_stacks && cell.iter().any(|c| *c != ' ')) {
+                    return Err(format!("Error: Bad crate '{}' in line '{}'",
+                        cell.iter().collect::<String>(), line));
+                }
+            }
+        }
 
-            // move crates according to move
-            for _ in 0..num {
-                let c = stacks[src - 1].pop().expect("Stack was empty?!");
+        let mut yard = CrateYard::from_lines(&lines);
+        yard.stacks.resize(num_stacks, Vec::new());
 
-                stacks[dst - 1].push(c);
+        Ok(yard)
+    }
+
+    fn check_move(&self, m: &Move) -> Result<(), String> {
+        for stack in [m.src, m.dst] {
+            if stack == 0 || stack > self.stacks.len() {
+                return Err(format!("Error: '{}': there is no stack {}", m, stack));
             }
         }
+        if m.src == m.dst {
+            return Err(format!("Error: '{}': source and destination are the same", m));
+        }
+
+        Ok(())
     }
 
-    for stack in stacks.iter() {
-        answer.push(
-            stack.last().copied().expect("Couldn't get last crate from stack"));
+    /*
+     * Apply a move with the given crane, returning the crates that were
+     * lifted (bottom to top, as they sat on the source) so it can be undone
+     */
+    fn apply(&mut self, m: &Move, crane: &dyn Crane) -> Result<Vec<char>, String> {
+        self.check_move(m)?;
+
+        let src = &mut self.stacks[m.src - 1];
+        if src.len() < m.num {
+            return Err(format!("Error: '{}': stack {} only has {} crates",
+                m, m.src, src.len()));
+        }
+
+        let lifted = src.split_off(src.len() - m.num);
+        self.stacks[m.dst - 1].extend(crane.arrange(&lifted));
+
+        Ok(lifted)
     }
 
-    // Print the answer to the first part
-    println!("First Answer: {:?}", answer);
+    // Reverse a move previously applied, given the crates it lifted
+    fn undo(&mut self, m: &Move, lifted: &[char]) -> Result<(), String> {
+        self.check_move(m)?;
+
+        let dst = &mut self.stacks[m.dst - 1];
+        if dst.len() < m.num {
+            return Err(format!("Error: Can't undo '{}': stack {} only has {} crates",
+                m, m.dst, dst.len()));
+        }
+
+        dst.truncate(dst.len() - m.num);
+        self.stacks[m.src - 1].extend_from_slice(lifted);
+
+        Ok(())
+    }
+
+    // Top crate of each stack, with a space for empty stacks
+    fn tops(&self) -> String {
+        self.stacks.iter()
+            .map(|s| s.last().copied().unwrap_or(' '))
+            .collect()
+    }
 }
 
+/*
+ * A log of moves which can be stepped through forwards and backwards. The
+ * crates lifted by each applied move are kept so any crane can be undone
+ */
+struct Replay<'c> {
+    yard: CrateYard,
+    moves: Vec<Move>,
+    lifted: Vec<Vec<char>>,
+    crane: &'c dyn Crane
+}
+
+impl <'c> Replay<'c> {
+
+    fn new(yard: CrateYard, moves: Vec<Move>, crane: &'c dyn Crane) -> Replay<'c> {
+        Replay {
+            yard,
+            moves,
+            lifted: Vec::new(),
+            crane
+        }
+    }
 
-fn part_2() {
+    // Number of moves applied so far
+    fn position(&self) -> usize {
+        self.lifted.len()
+    }
+
+    // Apply the next move, returning false if there are none left
+    fn step_forward(&mut self) -> Result<bool, String> {
+        let Some(m) = self.moves.get(self.position()) else {
+            return Ok(false);
+        };
+
+        let lifted = self.yard.apply(m, self.crane)
+            .map_err(|e| format!("{} (step {})", e, self.position() + 1))?;
+        self.lifted.push(lifted);
+
+        Ok(true)
+    }
+
+    // Undo the last applied move, returning false if at the start
+    fn step_back(&mut self) -> Result<bool, String> {
+        let Some(lifted) = self.lifted.pop() else {
+            return Ok(false);
+        };
+
+        let m = &self.moves[self.position()];
+        self.yard.undo(m, &lifted)?;
+
+        Ok(true)
+    }
+
+    // Step forwards or backwards until the given number of moves are applied
+    fn seek(&mut self, position: usize) -> Result<(), String> {
+        let position = position.min(self.moves.len());
+        while self.position() < position {
+            self.step_forward()?;
+        }
+        while self.position() > position {
+            self.step_back()?;
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        self.seek(self.moves.len())
+    }
+}
+
+fn read_file(filename: &str) -> Result<(CrateYard, Vec<Move>), String> {
     // Open the file
-    let file = File::open(FILENAME).unwrap();
+    let file = File::open(filename).unwrap();
     let reader = BufReader::new(file);
 
-    // init state for answer
-    let mut answer = String::new();
     let mut building_stacks = true;
-    let mut stacks = Vec::<Vec::<char>>::new();
+    let mut drawing = String::new();
+    let mut moves = Vec::new();
 
     // Read file line by line
     for line in reader.lines() {
         let line_str = line.expect("Couldn't read line as string?");
 
         if building_stacks {
-            // check for end of initial state
-            if line_str.starts_with(" 1 ") {
+            // the drawing ends with a blank line
+            if line_str.is_empty() {
                 building_stacks = false;
+            } else {
+                drawing.push_str(&line_str);
+                drawing.push('\n');
+            }
 
-                // print starting point
-                for (i, stack) in stacks.iter().enumerate() {
-                    println!("{}: {:?}", i, stack);
-                }
+        } else if !line_str.is_empty() {
+            moves.push(moves_from_str(&line_str)?);
+        }
+    }
 
-                continue;
-            }
-            
-            // split line into crates at this layer
-            let crates = crates_from_str(&line_str);
+    Ok((CrateYard::from_drawing(&drawing)?, moves))
+}
 
-            // add this layer of crates to stacks, inserting at "bottom"
-            for (i, c) in crates.iter().enumerate() {
-                // add stack if not present
-                while stacks.len() <= i {
-                    stacks.push(Vec::<char>::new());
-                }
+// Render a yard and moves as a complete puzzle input
+fn to_puzzle_input(yard: &CrateYard, moves: &[Move]) -> String {
+    let mut input = format!("{}\n\n", yard);
+    for m in moves.iter() {
+        input.push_str(&format!("{}\n", m));
+    }
 
-                if *c != ' ' {
-                    stacks[i].insert(0, *c);
-                }
+    input
+}
+
+// Print the yard after every move
+fn print_steps(yard: &CrateYard, moves: &[Move], crane: &dyn Crane) {
+    let mut replay = Replay::new(yard.clone(), moves.to_vec(), crane);
+    println!("{}\n", replay.yard);
+
+    loop {
+        let m = replay.moves.get(replay.position()).copied();
+        match replay.step_forward() {
+            Ok(true) => println!("{}\n\n{}\n", m.unwrap(), replay.yard),
+            Ok(false) => break,
+            Err(e) => {
+                println!("{}", e);
+                break;
             }
+        }
+    }
+}
 
-        } else {
-            if !line_str.starts_with("move") {
-                continue;
+fn crane_from_str(s: &str) -> Result<Box<dyn Crane>, String> {
+    match s {
+        "9000" => Ok(Box::new(CrateMover9000)),
+        "9001" => Ok(Box::new(CrateMover9001)),
+        _ => match s.parse::<usize>() {
+            Ok(max_lift) if max_lift > 0 => Ok(Box::new(MaxLiftCrane { max_lift })),
+            _ => Err(format!("Error: Unknown crane '{}'", s))
+        }
+    }
+}
+
+// What the planner is aiming for
+enum Target {
+    // The top crate of each stack, with a space for an empty stack
+    Tops(String),
+    // An exact arrangement of every crate
+    Yard(CrateYard)
+}
+
+impl Target {
+
+    fn reached(&self, yard: &CrateYard) -> bool {
+        match self {
+            Target::Tops(tops) => yard.tops() == *tops,
+            Target::Yard(target) => yard == target
+        }
+    }
+
+    // Rule out targets which can't be reached whatever the moves
+    fn check(&self, start: &CrateYard) -> Result<(), String> {
+        let mut counts = HashMap::new();
+        for c in start.stacks.iter().flatten() {
+            *counts.entry(*c).or_insert(0) += 1;
+        }
+
+        let (num_stacks, wanted): (usize, Vec<char>) = match self {
+            Target::Tops(tops) => (tops.chars().count(),
+                tops.chars().filter(|c| *c != ' ').collect()),
+            Target::Yard(target) => (target.stacks.len(),
+                target.stacks.iter().flatten().copied().collect())
+        };
+
+        if num_stacks != start.stacks.len() {
+            return Err(format!("Error: Target has {} stacks, the yard has {}",
+                num_stacks, start.stacks.len()));
+        }
+        for c in wanted {
+            match counts.get_mut(&c) {
+                Some(n) if *n > 0 => *n -= 1,
+                _ => return Err(format!("Error: Not enough '{}' crates for the target", c))
+            }
+        }
+        if let Target::Yard(_) = self {
+            if counts.values().any(|n| *n > 0) {
+                return Err("Error: Target drawing is missing crates".to_string());
             }
-            let (num, src, dst) = moves_from_str(&line_str);
+        }
 
-            // move crates according to move, preserving order
-            let mut to_move = Vec::<char>::new();
-            for _ in 0..num {
-                let c = stacks[src - 1].pop().expect("Stack was empty?!");
+        Ok(())
+    }
+}
 
-                to_move.insert(0, c);
+/*
+ * Breadth first search over yard states for the shortest list of moves
+ * that reaches the target with the given crane. Every move of 1 up to the
+ * whole stack between any two stacks is tried, so this gives up once
+ * max_states arrangements have been seen
+ */
+fn plan(
+        start: &CrateYard,
+        target: &Target,
+        crane: &dyn Crane,
+        max_states: usize) -> Result<Vec<Move>, String> {
+    target.check(start)?;
+
+    // each seen state maps to the state and move it was reached by
+    let mut seen: HashMap<CrateYard, Option<(CrateYard, Move)>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    seen.insert(start.clone(), None);
+    queue.push_back(start.clone());
+
+    while let Some(yard) = queue.pop_front() {
+        if target.reached(&yard) {
+            // walk back to the start to recover the moves
+            let mut moves = Vec::new();
+            let mut cur = &yard;
+            while let Some(Some((prev, m))) = seen.get(cur) {
+                moves.insert(0, *m);
+                cur = prev;
             }
 
-            for c in to_move.iter() {
-                stacks[dst - 1].push(*c);
+            return Ok(moves);
+        }
+
+        for src in 1..=yard.stacks.len() {
+            for dst in 1..=yard.stacks.len() {
+                if src == dst {
+                    continue;
+                }
+
+                for num in 1..=yard.stacks[src - 1].len() {
+                    let m = Move { num, src, dst };
+                    let mut next = yard.clone();
+                    next.apply(&m, crane)?;
+
+                    if seen.contains_key(&next) {
+                        continue;
+                    }
+                    if seen.len() >= max_states {
+                        return Err(format!("Error: Gave up after {} states", max_states));
+                    }
+
+                    seen.insert(next.clone(), Some((yard.clone(), m)));
+                    queue.push_back(next);
+                }
             }
         }
     }
 
-    for stack in stacks.iter() {
-        answer.push(
-            stack.last().copied().expect("Couldn't get last crate from stack"));
-    }
+    Err(format!("Error: Target can't be reached with the {}", crane.name()))
+}
 
-    // Print the answer to the second part
-    println!("Second Answer: {:?}", answer);
+fn run_crane(yard: &CrateYard, moves: &[Move], crane: &dyn Crane) -> Result<String, String> {
+    let mut replay = Replay::new(yard.clone(), moves.to_vec(), crane);
+    replay.run()?;
+
+    Ok(replay.yard.tops())
 }
 
 fn main() {
-    println!("Advent of Code, Day 4");
+    println!("Advent of Code, Day 5");
+
+    // "steps [file]" prints the drawing after each CrateMover 9000 move,
+    // "render [file]" re-renders the file as a puzzle input
+    let args: Vec<String> = env::args().collect();
+    let filename = args.get(2).map(|f| f.as_str()).unwrap_or(FILENAME);
+    match args.get(1).map(|a| a.as_str()) {
+        Some("steps") => {
+            let (yard, moves) = read_file(filename).unwrap();
+            print_steps(&yard, &moves, &CrateMover9000);
+            return;
+        },
+        Some("render") => {
+            let (yard, moves) = read_file(filename).unwrap();
+            print!("{}", to_puzzle_input(&yard, &moves));
+            return;
+        },
+        Some("plan") => {
+            // "plan <file> <target> [crane]" where the target is either a
+            // file holding a drawing, or a string of the wanted top crates
+            let (yard, _) = read_file(filename).unwrap();
+            let target = args.get(3).expect("No target given");
+            let target = match fs::read_to_string(target) {
+                Ok(drawing) => Target::Yard(CrateYard::from_drawing(&drawing).unwrap()),
+                Err(_) => Target::Tops(target.to_string())
+            };
+            let crane = crane_from_str(
+                args.get(4).map(|c| c.as_str()).unwrap_or("9000")).unwrap();
+
+            match plan(&yard, &target, crane.as_ref(), MAX_PLAN_STATES) {
+                Ok(moves) => print!("{}", to_puzzle_input(&yard, &moves)),
+                Err(e) => println!("{}", e)
+            }
+            return;
+        },
+        _ => ()
+    }
+
+    let (yard, moves) = read_file(FILENAME).unwrap();
 
-    part_1();
-    part_2();
+    // print starting point
+    for (i, stack) in yard.stacks.iter().enumerate() {
+        println!("{}: {:?}", i, stack);
+    }
+
+    // Print the answer to the first part
+    let crane = CrateMover9000;
+    let answer = run_crane(&yard, &moves, &crane).unwrap();
+    println!("First Answer ({}): {:?}", crane.name(), answer);
+
+    // Print the answer to the second part
+    let crane = CrateMover9001;
+    let answer = run_crane(&yard, &moves, &crane).unwrap();
+    println!("Second Answer ({}): {:?}", crane.name(), answer);
+
+    let crane = MaxLiftCrane { max_lift: 3 };
+    match run_crane(&yard, &moves, &crane) {
+        Ok(answer) => println!("{}: {:?}", crane.name(), answer),
+        Err(e) => println!("{}: {}", crane.name(), e)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_cranes() {
+        let (yard, moves) = read_file("./test").unwrap();
+
+        assert_eq!(run_crane(&yard, &moves, &CrateMover9000), Ok("CMZ".to_string()));
+        assert_eq!(run_crane(&yard, &moves, &CrateMover9001), Ok("MCD".to_string()));
+        assert_eq!(run_crane(&yard, &moves, &MaxLiftCrane { max_lift: 1 }),
+            Ok("CMZ".to_string()));
+        assert_eq!(run_crane(&yard, &moves, &MaxLiftCrane { max_lift: 3 }),
+            Ok("MCD".to_string()));
+
+        assert_eq!(MaxLiftCrane { max_lift: 2 }.arrange(&['A', 'B', 'C']),
+            vec!['B', 'C', 'A']);
+    }
+
+    #[test]
+    fn test_replay_undo() {
+        let (yard, moves) = read_file("./test").unwrap();
+        let crane = MaxLiftCrane { max_lift: 2 };
+        let mut replay = Replay::new(yard.clone(), moves, &crane);
+
+        replay.run().unwrap();
+        assert_eq!(replay.position(), 4);
+        assert_eq!(replay.step_forward(), Ok(false));
+
+        replay.seek(1).unwrap();
+        assert_eq!(replay.yard.tops(), "DCP");
+
+        replay.seek(0).unwrap();
+        assert_eq!(replay.yard, yard);
+        assert_eq!(replay.step_back(), Ok(false));
+    }
+
+    #[test]
+    fn test_drawing_round_trip() {
+        let test = std::fs::read_to_string("./test").unwrap();
+        let (yard, moves) = read_file("./test").unwrap();
+
+        assert_eq!(to_puzzle_input(&yard, &moves), test);
+
+        let input = std::fs::read_to_string("./input").unwrap();
+        let (yard, moves) = read_file("./input").unwrap();
+        assert_eq!(to_puzzle_input(&yard, &moves), input);
+    }
+
+    #[test]
+    fn test_intermediate_drawings() {
+        let (yard, moves) = read_file("./test").unwrap();
+        let mut replay = Replay::new(yard, moves, &CrateMover9000);
+
+        replay.seek(2).unwrap();
+        assert_eq!(replay.yard.to_string(), "        [Z]
+        [N]
+    [C] [D]
+    [M] [P]
+ 1   2   3 ");
+
+        replay.run().unwrap();
+        let expected = CrateYard::from_drawing("        [Z]
+        [N]
+        [D]
+[C] [M] [P]
+ 1   2   3 ").unwrap();
+        assert_eq!(replay.yard, expected);
+    }
+
+    #[test]
+    fn test_bad_drawings() {
+        assert!(CrateYard::from_drawing("").is_err());
+        assert!(CrateYard::from_drawing("[A] [B]\n 1   3 ").is_err());
+        assert!(CrateYard::from_drawing("[A] [B] [C]\n 1   2 ").is_err());
+        assert!(CrateYard::from_drawing("[A] (B)\n 1   2 ").is_err());
+
+        // a stack that is empty in every row still exists
+        let yard = CrateYard::from_drawing("[A]\n 1   2 ").unwrap();
+        assert_eq!(yard.stacks, vec![vec!['A'], vec![]]);
+        assert_eq!(yard.to_string(), "[A]    \n 1   2 ");
+    }
+
+    #[test]
+    fn test_plan() {
+        let (yard, moves) = read_file("./test").unwrap();
+
+        for crane in ["9000", "9001", "2"] {
+            let crane = crane_from_str(crane).unwrap();
+            let tops = run_crane(&yard, &moves, crane.as_ref()).unwrap();
+
+            let plan_moves = plan(&yard, &Target::Tops(tops.clone()),
+                crane.as_ref(), MAX_PLAN_STATES).unwrap();
+            assert!(plan_moves.len() <= moves.len());
+            assert_eq!(run_crane(&yard, &plan_moves, crane.as_ref()), Ok(tops));
+
+            let mut replay = Replay::new(yard.clone(), moves.clone(), crane.as_ref());
+            replay.run().unwrap();
+            let target = Target::Yard(replay.yard.clone());
+            let plan_moves = plan(&yard, &target, crane.as_ref(), MAX_PLAN_STATES).unwrap();
+            assert!(plan_moves.len() <= moves.len());
+
+            let mut replay = Replay::new(yard.clone(), plan_moves, crane.as_ref());
+            replay.run().unwrap();
+            assert!(target.reached(&replay.yard));
+        }
+
+        // Z is under N, which has to end up below D on stack 2
+        let plan_moves = plan(&yard, &Target::Tops("ZDP".to_string()),
+            &CrateMover9001, MAX_PLAN_STATES).unwrap();
+        assert_eq!(plan_moves, vec![
+            Move { num: 1, src: 2, dst: 1 },
+            Move { num: 2, src: 1, dst: 2 }]);
+    }
+
+    #[test]
+    fn test_impossible_plans() {
+        let (yard, _) = read_file("./test").unwrap();
+
+        assert!(plan(&yard, &Target::Tops("XMZ".to_string()),
+            &CrateMover9000, MAX_PLAN_STATES).is_err());
+        assert!(plan(&yard, &Target::Tops("ZZ".to_string()),
+            &CrateMover9000, MAX_PLAN_STATES).is_err());
+        assert!(plan(&yard, &Target::Tops("ZZN".to_string()),
+            &CrateMover9000, MAX_PLAN_STATES).is_err());
+        assert!(plan(&yard, &Target::Tops("CMZ".to_string()),
+            &CrateMover9000, 10).is_err());
+    }
+
+    #[test]
+    fn test_invalid_moves() {
+        let (yard, _) = read_file("./test").unwrap();
+
+        for m in ["move 4 from 1 to 2", "move 1 from 4 to 2", "move 1 from 0 to 2",
+                "move 1 from 2 to 2"] {
+            let mut y = yard.clone();
+            assert!(y.apply(&moves_from_str(m).unwrap(), &CrateMover9000).is_err());
+            assert_eq!(y, yard);
+        }
+
+        assert!(moves_from_str("move x from 1 to 2").is_err());
+        assert!(moves_from_str("take 1 from 1 to 2").is_err());
+    }
+}
@@ -1,104 +1,395 @@
+use std::env;
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::io::{self, prelude::*, BufReader};
 use std::collections::VecDeque;
 
 const FILENAME: &str = "./input";
+const PACKET_WINDOW: usize = 4;
+const MESSAGE_WINDOW: usize = 14;
+const FRAME_LIMIT: usize = 4096;
 
-fn start_of_packet(x: &str) -> Option<usize> {
-    let mut seq = VecDeque::<char>::new();
+/*
+ * Tracks the last `window` bytes of a stream and whether they are all
+ * different. A count of each byte value in the window, plus a count of how
+ * many values appear more than once, makes each new byte O(1)
+ */
+struct MarkerDetector {
+    window: usize,
+    seq: VecDeque<u8>,
+    counts: [usize; 256],
+    repeats: usize,
+    position: usize
+}
+
+impl MarkerDetector {
+
+    fn new(window: usize) -> MarkerDetector {
+        assert!(window > 0, "Marker window must not be empty");
+
+        MarkerDetector {
+            window,
+            seq: VecDeque::with_capacity(window + 1),
+            counts: [0; 256],
+            repeats: 0,
+            position: 0
+        }
+    }
 
-    for (i, c) in x.chars().enumerate() {
-        if !seq.contains(&c) && seq.len() == 3 {
-            // if this is the fourth unique character, then this is the start of packet
-            return Some(i + 1);
+    /*
+     * Add the next byte of the stream, returning the number of bytes seen
+     * so far if the last `window` of them are all different
+     */
+    fn push(&mut self, b: u8) -> Option<usize> {
+        self.position += 1;
 
-        } else if seq.contains(&c) {
-            // remove all characters at the beginning of the sequence until we
-            // remove the repeated character
-            while seq.contains(&c) {
-                seq.pop_front();
+        self.seq.push_back(b);
+        self.counts[b as usize] += 1;
+        if self.counts[b as usize] == 2 {
+            self.repeats += 1;
+        }
+
+        if self.seq.len() > self.window {
+            let old = self.seq.pop_front().unwrap();
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] == 1 {
+                self.repeats -= 1;
             }
         }
 
-        // insert at zero for a FIFO queue
-        seq.push_back(c);
+        if self.seq.len() == self.window && self.repeats == 0 {
+            Some(self.position)
+        } else {
+            None
+        }
     }
+}
 
-    None
+/*
+ * Every marker in a datastream read from any source. Line breaks are not
+ * part of the datastream, so they are skipped and not counted
+ */
+struct Markers<R: Read> {
+    bytes: io::Bytes<BufReader<R>>,
+    detector: MarkerDetector
 }
 
-fn start_of_message(x: &str) -> Option<usize> {
-    let mut seq = VecDeque::<char>::new();
+impl <R: Read> Markers<R> {
 
-    for (i, c) in x.chars().enumerate() {
-        if !seq.contains(&c) && seq.len() == 13 {
-            // if this is the fourteenth unique character, then this is the start of message
-            return Some(i + 1);
+    fn new(reader: R, window: usize) -> Markers<R> {
+        Markers {
+            bytes: BufReader::new(reader).bytes(),
+            detector: MarkerDetector::new(window)
+        }
+    }
+}
+
+impl <R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for b in self.bytes.by_ref() {
+            let b = match b {
+                Ok(b) => b,
+                Err(e) => return Some(Err(e))
+            };
+            if b == b'\n' || b == b'\r' {
+                continue;
+            }
 
-        } else if seq.contains(&c) {
-            // remove all characters at the beginning of the sequence until we
-            // remove the repeated character
-            while seq.contains(&c) {
-                seq.pop_front();
+            if let Some(i) = self.detector.push(b) {
+                return Some(Ok(i));
             }
         }
 
-        // insert at zero for a FIFO queue
-        seq.push_back(c);
+        None
     }
+}
+
+/*
+ * Find the first marker that starts no more than `limit` bytes into the
+ * stream, returning the offset of its first byte
+ */
+fn find_marker(stream: &[u8], window: usize, limit: usize) -> Option<usize> {
+    let end = stream.len().min(limit.saturating_add(window));
+    let mut detector = MarkerDetector::new(window);
 
-    None
+    stream[..end].iter()
+        .find_map(|b| detector.push(*b))
+        .map(|i| i - window)
 }
 
-fn part_1() {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum SegmentKind {
+    // Bytes after a marker, up to the next marker or the end of the stream
+    Frame,
+    // Bytes before the first marker
+    Unframed,
+    // `limit` bytes (at least one) in which no marker started. If these
+    // follow a marker directly, that marker gets no frame of its own and
+    // is only given as the segment's marker
+    Corrupt
+}
 
-    // init state for answer
-    let mut answer = 0;
+#[derive(Debug,Clone,PartialEq,Eq)]
+struct Segment<'a> {
+    kind: SegmentKind,
+    // Offset of the marker which starts a frame
+    marker: Option<usize>,
+    // Offset of the first byte of data
+    offset: usize,
+    data: &'a [u8]
+}
+
+/*
+ * Splits a datastream into the frames that follow each marker. When no
+ * marker starts within `limit` bytes, that many bytes are reported as
+ * corrupt and the search for a marker starts again after them. A limit
+ * of 0 reports one byte at a time. A marker followed straight away by
+ * corrupt bytes has no frame, only the corrupt segment naming it
+ */
+struct Framer<'a> {
+    stream: &'a [u8],
+    window: usize,
+    limit: usize,
+    // Offset of the stream within the whole datastream
+    base: usize,
+    pos: usize,
+    // Offset of the marker of the frame that starts at pos, if any
+    marker: Option<usize>
+}
 
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
+impl <'a> Framer<'a> {
 
-        let start_char = start_of_packet(&line_str);
-        if let Some(i) = start_char {
-            answer = i;
+    fn new(stream: &'a [u8], window: usize, limit: usize, base: usize) -> Framer<'a> {
+        Framer {
+            stream,
+            window,
+            limit,
+            base,
+            pos: 0,
+            marker: None
         }
     }
 
-    // Print the answer to the first part
-    println!("First Answer: {:?}", answer);
+    fn segment(&self, kind: SegmentKind, end: usize) -> Segment<'a> {
+        Segment {
+            kind,
+            marker: self.marker.map(|m| self.base + m),
+            offset: self.base + self.pos,
+            data: &self.stream[self.pos..end]
+        }
+    }
 }
 
+impl <'a> Iterator for Framer<'a> {
+    type Item = Segment<'a>;
 
-fn part_2() {
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos >= self.stream.len() && self.marker.is_none() {
+                return None;
+            }
+
+            let rest = &self.stream[self.pos..];
+            let found = find_marker(rest, self.window, self.limit)
+                .map(|m| self.pos + m);
+
+            let (kind, end) = match found {
+                Some(m) if self.marker.is_some() => (SegmentKind::Frame, m),
+                Some(m) if m == self.pos => {
+                    // no data before this marker, so go straight to its frame
+                    self.marker = Some(m);
+                    self.pos = m + self.window;
+                    continue;
+                },
+                Some(m) => (SegmentKind::Unframed, m),
+                // at least one byte, so a limit of 0 still moves on
+                None if rest.len() > self.limit =>
+                    (SegmentKind::Corrupt, self.pos + self.limit.max(1)),
+                None if self.marker.is_some() =>
+                    (SegmentKind::Frame, self.stream.len()),
+                None => (SegmentKind::Unframed, self.stream.len())
+            };
+
+            let segment = self.segment(kind, end);
+            match (kind, found) {
+                (SegmentKind::Corrupt, _) | (_, None) => {
+                    self.marker = None;
+                    self.pos = end;
+                },
+                (_, Some(m)) => {
+                    self.marker = Some(m);
+                    self.pos = m + self.window;
+                }
+            }
+
+            return Some(segment);
+        }
+    }
+}
+
+// Read a whole datastream, dropping the line breaks
+fn read_datastream(filename: &str) -> Vec<u8> {
+    let mut stream = Vec::new();
     // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+    let file = File::open(filename).unwrap();
+    for b in BufReader::new(file).bytes() {
+        let b = b.expect("Couldn't read from file?");
+        if b != b'\n' && b != b'\r' {
+            stream.push(b);
+        }
+    }
 
-    // init state for answer
-    let mut answer = 0;
+    stream
+}
 
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
+fn print_segment(indent: &str, name: &str, segment: &Segment) {
+    let data = String::from_utf8_lossy(segment.data);
+    match segment.kind {
+        SegmentKind::Frame => println!("{}{} at {} (marker at {}): {}",
+            indent, name, segment.offset, segment.marker.unwrap(), data),
+        SegmentKind::Unframed => println!("{}Unframed at {}: {}",
+            indent, segment.offset, data),
+        SegmentKind::Corrupt => println!("{}Corrupt at {}: {}",
+            indent, segment.offset, data)
+    }
+}
+
+// Print the messages in a datastream, and the packets in each message
+fn print_frames(stream: &[u8], limit: usize) {
+    for message in Framer::new(stream, MESSAGE_WINDOW, limit, 0) {
+        print_segment("", "Message", &message);
+        if message.kind != SegmentKind::Frame {
+            continue;
+        }
 
-        let start_char = start_of_message(&line_str);
-        if let Some(i) = start_char {
-            answer = i;
+        for packet in Framer::new(message.data, PACKET_WINDOW, limit, message.offset) {
+            print_segment("    ", "Packet", &packet);
         }
     }
+}
+
+fn first_marker_in_file(filename: &str, window: usize) -> Option<usize> {
+    // Open the file
+    let file = File::open(filename).unwrap();
+
+    Markers::new(file, window).next()
+        .map(|m| m.expect("Couldn't read from file?"))
+}
+
+fn part_1() {
+    let answer = first_marker_in_file(FILENAME, PACKET_WINDOW).unwrap_or(0);
+
+    // Print the answer to the first part
+    println!("First Answer: {:?}", answer);
+}
+
+
+fn part_2() {
+    let answer = first_marker_in_file(FILENAME, MESSAGE_WINDOW).unwrap_or(0);
 
     // Print the answer to the second part
     println!("Second Answer: {:?}", answer);
 }
 
 fn main() {
-    println!("Advent of Code, Day 4");
+    println!("Advent of Code, Day 6");
+
+    // "frames [file] [limit]" splits the datastream into messages and packets
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(|a| a.as_str()) == Some("frames") {
+        let filename = args.get(2).map(|f| f.as_str()).unwrap_or(FILENAME);
+        let limit = args.get(3).map(|l| l.parse().expect("Bad limit"))
+            .unwrap_or(FRAME_LIMIT);
+        print_frames(&read_datastream(filename), limit);
+        return;
+    }
 
     part_1();
     part_2();
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    const EXAMPLES: [(&str, usize, usize); 4] = [
+        ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+        ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+        ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+        ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26)
+    ];
+
+    fn first_marker(x: &str, window: usize) -> Option<usize> {
+        Markers::new(x.as_bytes(), window).next().map(|m| m.unwrap())
+    }
+
+    #[test]
+    fn test_examples() {
+        for (stream, packet, message) in EXAMPLES {
+            assert_eq!(first_marker(stream, PACKET_WINDOW), Some(packet));
+            assert_eq!(first_marker(stream, MESSAGE_WINDOW), Some(message));
+        }
+
+        assert_eq!(first_marker("aaaaaaaa", PACKET_WINDOW), None);
+        assert_eq!(first_marker_in_file("./test", PACKET_WINDOW), Some(7));
+    }
+
+    #[test]
+    fn test_framing() {
+        let stream = b"aaaabcdxxyxzwzzzzzzzzzzzijklm";
+        let frames: Vec<Segment> = Framer::new(stream, PACKET_WINDOW, 8, 0).collect();
+
+        let expected = vec![
+            (SegmentKind::Unframed, None, 0, &b"aaa"[..]),
+            (SegmentKind::Frame, Some(3), 7, &b"xx"[..]),
+            (SegmentKind::Corrupt, Some(9), 13, &b"zzzzzzzz"[..]),
+            (SegmentKind::Unframed, None, 21, &b"zz"[..]),
+            (SegmentKind::Frame, Some(23), 27, &b"lm"[..])
+        ];
+        assert_eq!(frames.len(), expected.len());
+        for (f, (kind, marker, offset, data)) in frames.iter().zip(expected) {
+            assert_eq!((f.kind, f.marker, f.offset, f.data), (kind, marker, offset, data));
+        }
+
+        // with no room for frames, everything but the markers is corrupt
+        let segments: Vec<(usize, &[u8])> = Framer::new(b"aaabcdxx", PACKET_WINDOW, 0, 0)
+            .map(|s| {
+                assert_eq!(s.kind, SegmentKind::Corrupt);
+                (s.offset, s.data)
+            })
+            .collect();
+        assert_eq!(segments, vec![(0, &b"a"[..]), (1, &b"a"[..]), (6, &b"x"[..]), (7, &b"x"[..])]);
+
+        // every byte not in a marker is in exactly one segment
+        let input = read_datastream("./input");
+        let segments: Vec<Segment> = Framer::new(&input, MESSAGE_WINDOW, FRAME_LIMIT, 0).collect();
+        let markers = segments.iter().filter(|s| s.kind == SegmentKind::Frame).count();
+        let total: usize = segments.iter().map(|s| s.data.len()).sum();
+        assert_eq!(total + markers * MESSAGE_WINDOW, input.len());
+        assert_eq!(segments[0].marker, None);
+        assert_eq!(segments[1].offset, 3559);
+    }
+
+    #[test]
+    fn test_all_markers() {
+        let input = std::fs::read_to_string("./input").unwrap();
+        let stream = input.trim().as_bytes();
+
+        for window in [1, 2, PACKET_WINDOW, MESSAGE_WINDOW, 20] {
+            let expected: Vec<usize> = stream.windows(window).enumerate()
+                .filter(|(_, w)| {
+                    let mut seen = [false; 256];
+                    w.iter().all(|b| !std::mem::replace(&mut seen[*b as usize], true))
+                })
+                .map(|(i, _)| i + window)
+                .collect();
+
+            let markers: Vec<usize> = Markers::new(input.as_bytes(), window)
+                .map(|m| m.unwrap())
+                .collect();
+            assert_eq!(markers, expected);
+        }
+    }
+}
@@ -1,387 +1,1057 @@
-use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader};
+use std::path::Path;
 
 const FILENAME: &str = "./input";
 
+// Index of an entry in the filesystem arena
+type Inode = usize;
+
+const ROOT: Inode = 0;
+
 #[derive(Debug)]
 struct DirEntry {
-    path: String,
+    name: String,
+    parent: Option<Inode>,
     file_size: usize,
     is_dir: bool,
-    entries: Vec<DirEntry>,
+    // whether `ls` has been run in this directory
+    listed: bool,
+    entries: Vec<Inode>,
 }
 
 impl DirEntry {
-    fn size(&self) -> usize {
-        if !self.is_dir {
-            self.file_size
-        } else {
-            let mut rval = 0;
-            for e in self.entries.iter() {
-                rval += e.size();
-            }
-
-            rval
+    fn new_file(name: &str, parent: Inode, file_size: usize) -> Self {
+        Self {
+            name: String::from(name),
+            parent: Some(parent),
+            file_size,
+            is_dir: false,
+            listed: false,
+            entries: Vec::<Inode>::new(),
         }
     }
 
-    #[allow(dead_code)]
-    fn eq(&self, other: &Self) -> bool {
-        self.path.eq(&other.path)
+    fn new_dir(name: &str, parent: Option<Inode>) -> Self {
+        Self {
+            name: String::from(name),
+            parent,
+            file_size: 0,
+            is_dir: true,
+            listed: false,
+            entries: Vec::<Inode>::new(),
+        }
     }
+}
 
-    #[allow(dead_code)]
-    fn is_path(&self, path: &str) -> bool {
-        if self.path.eq(path) {
-            return true;
+/*
+ * A filesystem rebuilt from a terminal transcript. Entries live in one
+ * arena and refer to each other by inode, and the total size of every
+ * directory is kept up to date as files are added
+ */
+#[derive(Debug)]
+struct Vfs {
+    nodes: Vec<DirEntry>,
+    sizes: Vec<usize>,
+    cwd: Inode,
+}
+
+impl Vfs {
+    fn new() -> Self {
+        Self {
+            nodes: vec![DirEntry::new_dir("", None)],
+            sizes: vec![0],
+            cwd: ROOT,
         }
+    }
 
-        false
+    fn size(&self, id: Inode) -> usize {
+        self.sizes[id]
     }
 
-    fn new_file(path: &str, file_size: usize) -> Self {
-        Self {
-            path: String::from(path),
-            file_size,
-            is_dir: false,
-            entries: Vec::<DirEntry>::new(),
-        }
+    fn child(&self, dir: Inode, name: &str) -> Option<Inode> {
+        self.nodes[dir].entries.iter()
+            .copied()
+            .find(|e| self.nodes[*e].name == name)
     }
 
-    fn new_dir(path: &str) -> Self {
-        let mut dir_path = String::from(path);
-        if !dir_path.ends_with('/') {
-            dir_path.push('/');
+    fn path(&self, id: Inode) -> String {
+        let mut names = Vec::new();
+        let mut cur = id;
+        while let Some(parent) = self.nodes[cur].parent {
+            names.push(self.nodes[cur].name.as_str());
+            cur = parent;
         }
-        Self {
-            path: dir_path,
-            file_size: 0,
-            is_dir: true,
-            entries: Vec::<DirEntry>::new(),
+        names.reverse();
+
+        let mut path = format!("/{}", names.join("/"));
+        if self.nodes[id].is_dir && id != ROOT {
+            path.push('/');
         }
+
+        path
     }
 
-    fn add_dir(&mut self, dir: &mut Self) -> bool {
-        if self.is_dir {
-            if self.path.eq(&dir.path) {
-                while let Some(e) = dir.entries.pop() {
-                    self.entries.insert(0, e);
-                }
-                return true;
+    /*
+     * Find the entry at `/`, `..` or a path of directories relative to
+     * `from`. Only directories which have been listed can be entered, and
+     * `..` from the root stays at the root
+     */
+    fn resolve(&self, from: Inode, path: &str) -> Result<Inode, String> {
+        let mut cur = from;
+        if path.starts_with('/') {
+            cur = ROOT;
+        }
+
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            if !self.nodes[cur].is_dir {
+                return Err(format!("{} is not a directory", self.path(cur)));
             }
 
-            for i in 0..self.entries.len() {
-                if self.entries[i].add_dir(dir) {
-                    return true;
+            cur = match name {
+                "." => cur,
+                ".." => self.nodes[cur].parent.unwrap_or(ROOT),
+                _ => match self.child(cur, name) {
+                    Some(c) => c,
+                    None if self.nodes[cur].listed => return Err(format!(
+                        "{}{} is not in the listing", self.path(cur), name)),
+                    None => return Err(format!(
+                        "{}{}: {} was never listed",
+                        self.path(cur), name, self.path(cur)))
                 }
-            }
+            };
         }
 
-        false
+        Ok(cur)
     }
 
-    fn print_dir(d: &Self, prefix: &str) {
-        for e in d.entries.iter() {
-            if e.is_dir {
-                let mut new_prefix = String::from("  ");
-                new_prefix.push_str(prefix);
+    fn cd(&mut self, path: &str) -> Result<(), String> {
+        let dir = self.resolve(self.cwd, path)
+            .map_err(|e| format!("Can't cd: {}", e))?;
+        if !self.nodes[dir].is_dir {
+            return Err(format!("Can't cd into file {}", self.path(dir)));
+        }
+        self.cwd = dir;
 
-                println!("{}- {} (dir)", prefix, e.path);
-                DirEntry::print_dir(e, &new_prefix);
+        Ok(())
+    }
 
-            } else {
-                println!("{}- {} (file, size={})", prefix, e.path, e.size());
+    // Every entry below `top` with its depth, parents before their children
+    fn walk(&self, top: Inode) -> Vec<(Inode, usize)> {
+        let mut entries = Vec::new();
+        let mut to_visit = vec![(top, 0)];
+        while let Some((e, depth)) = to_visit.pop() {
+            entries.push((e, depth));
+            for c in self.nodes[e].entries.iter().rev() {
+                to_visit.push((*c, depth + 1));
             }
         }
-    }
 
-    fn print(&self) {
-        println!("- {} (dir)", self.path);
-
-        DirEntry::print_dir(self, "  ");
+        entries
     }
-}
 
-fn part_1() -> usize {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
-
-    // track current directory
-    let mut pwd = String::new();
-    let mut proc_output = false;
-    let mut root = DirEntry::new_dir("/");
-    let mut cur_dir = DirEntry::new_dir("/");
+    // Start an `ls` of the current directory
+    fn ls(&mut self) {
+        self.nodes[self.cwd].listed = true;
+    }
 
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
-        let line_str = line_str.trim();
+    /*
+     * Add one line of `ls` output to the current directory. An entry that
+     * is listed again has to match what was listed before
+     */
+    fn add_entry(&mut self, line: &str) -> Result<Inode, String> {
+        let (kind, name) = line.split_once(' ')
+            .ok_or(format!("Error splitting LS output: {}", line))?;
+        let dir = self.cwd;
+
+        // names end up in paths, so they can't move around the tree
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(format!("Invalid name in LS output: {}", line));
+        }
 
-        if let Some(dir_str) = line_str.strip_prefix("$ cd ") {
+        let file_size = if kind == "dir" {
+            None
+        } else {
+            Some(kind.parse::<usize>()
+                .map_err(|_| format!("Couldn't parse file size: {}", line))?)
+        };
+
+        if let Some(existing) = self.child(dir, name) {
+            let e = &self.nodes[existing];
+            return match file_size {
+                None if e.is_dir => Ok(existing),
+                Some(sz) if !e.is_dir && e.file_size == sz => Ok(existing),
+                None => Err(format!("{} was listed as a file and a dir",
+                    self.path(existing))),
+                Some(_) if e.is_dir => Err(format!("{} was listed as a dir and a file",
+                    self.path(existing))),
+                Some(sz) => Err(format!("{} was listed with sizes {} and {}",
+                    self.path(existing), e.file_size, sz))
+            };
+        }
 
-            // insert previously listed directory into tree
-            if proc_output {
-                if !root.add_dir(&mut cur_dir) {
-                    println!("Error: couldn't add dir {:?}", cur_dir);
-                    return 0;
+        let id = self.nodes.len();
+        match file_size {
+            None => self.nodes.push(DirEntry::new_dir(name, Some(dir))),
+            Some(sz) => {
+                self.nodes.push(DirEntry::new_file(name, dir, sz));
+
+                // update the cached size of every directory above the file
+                let mut cur = Some(dir);
+                while let Some(d) = cur {
+                    self.sizes[d] += sz;
+                    cur = self.nodes[d].parent;
                 }
-                cur_dir = DirEntry::new_dir("/");
             }
-            proc_output = false;
+        }
+        self.sizes.push(file_size.unwrap_or(0));
+        self.nodes[dir].entries.push(id);
 
-            let dir_str = dir_str.trim();
-            if dir_str.is_empty() {
-                // invalid `cd` - no change to state
-                continue;
-            }
+        Ok(id)
+    }
 
-            if dir_str.starts_with('/') {
-                pwd.clear();
-                pwd.push_str(dir_str);
+    fn from_transcript(lines: &[String]) -> Result<Self, String> {
+        let mut vfs = Vfs::new();
+        let mut proc_output = false;
 
-            } else if dir_str.starts_with("..") {
-                if pwd.len() < 2 {
-                    // no parent directories here!
-                    continue;
-                }
+        for (i, line) in lines.iter().enumerate() {
+            let line_str = line.trim();
+            let at_line = |e: String| format!("Error on line {}: {}", i + 1, e);
 
-                // remove trailing slash
-                if pwd.ends_with('/') {
-                    pwd.pop();
-                }
+            if let Some(dir_str) = line_str.strip_prefix("$ cd ") {
+                proc_output = false;
+                vfs.cd(dir_str.trim()).map_err(at_line)?;
 
-                // find previous directory marker
-                let par_dir = pwd.rfind('/');
+            } else if line_str == "$ ls" {
+                proc_output = true;
+                vfs.ls();
 
-                // truncate string to marker from above
-                if let Some(dir_mark) = par_dir {
-                    pwd.truncate(dir_mark + 1);
-                }
+            } else if proc_output {
+                vfs.add_entry(line_str).map_err(at_line)?;
 
-            } else {
-                pwd.push_str(dir_str);
-                pwd.push('/');
+            } else if !line_str.is_empty() {
+                return Err(at_line(format!("Invalid state: '{}'", line_str)));
             }
+        }
 
-        } else if line_str.starts_with("$ ls") {
-            proc_output = true;
-            println!("ls called for: {}", pwd);
+        Ok(vfs)
+    }
+
+    fn print_dir(&self, d: Inode, prefix: &str) {
+        for e in self.nodes[d].entries.iter() {
+            let e = *e;
+            if self.nodes[e].is_dir {
+                let mut new_prefix = String::from("  ");
+                new_prefix.push_str(prefix);
 
-            cur_dir = DirEntry::new_dir(&pwd);
+                println!("{}- {} (dir)", prefix, self.nodes[e].name);
+                self.print_dir(e, &new_prefix);
 
-        } else if proc_output {
-            let splits: Vec<&str> = line_str.split(' ').collect();
-            if splits.len() != 2 {
-                println!("Error splitting LS output: {}", line_str);
-                return 0;
+            } else {
+                println!("{}- {} (file, size={})", prefix, self.nodes[e].name, self.size(e));
             }
-            let mut path = String::from(&pwd);
+        }
+    }
 
-            if line_str.starts_with("dir ") {
-                path.push_str(splits[1]);
+    fn print(&self) {
+        println!("- / (dir)");
 
-                let d = DirEntry::new_dir(&path);
-                println!("  {:?}", d);
+        self.print_dir(ROOT, "  ");
+    }
+}
 
-                cur_dir.entries.push(d);
+// Size with a binary unit suffix, like `tree -h` or `du -h`
+fn human_size(size: usize) -> String {
+    let units = ["B", "K", "M", "G", "T"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
 
-            } else {
-                let file_size = splits[0].parse().expect("Couldn't parse file size");
-                path.push_str(splits[1]);
+    if unit == 0 {
+        format!("{}{}", size, units[unit])
+    } else if value < 10.0 {
+        format!("{:.1}{}", value, units[unit])
+    } else {
+        format!("{:.0}{}", value, units[unit])
+    }
+}
 
-                let f = DirEntry::new_file(&path, file_size);
-                println!("  {:?}", f);
+fn json_string(s: &str) -> String {
+    let mut rval = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => rval.push_str("\\\""),
+            '\\' => rval.push_str("\\\\"),
+            c if (c as u32) < 0x20 => rval.push_str(&format!("\\u{:04x}", c as u32)),
+            c => rval.push(c)
+        }
+    }
+    rval.push('"');
 
-                cur_dir.entries.push(f);
-            }
+    rval
+}
+
+impl Vfs {
+    fn json_entry(&self, e: Inode, indent: usize, out: &mut String) {
+        let pad = " ".repeat(indent);
+        let name = if e == ROOT { "/" } else { self.nodes[e].name.as_str() };
+
+        out.push_str(&format!("{}{{\n", pad));
+        out.push_str(&format!("{}  \"name\": {},\n", pad, json_string(name)));
+        if !self.nodes[e].is_dir {
+            out.push_str(&format!("{}  \"type\": \"file\",\n", pad));
+            out.push_str(&format!("{}  \"size\": {}\n", pad, self.size(e)));
+            out.push_str(&format!("{}}}", pad));
+            return;
+        }
 
+        out.push_str(&format!("{}  \"type\": \"dir\",\n", pad));
+        out.push_str(&format!("{}  \"size\": {},\n", pad, self.size(e)));
+        if self.nodes[e].entries.is_empty() {
+            out.push_str(&format!("{}  \"entries\": []\n", pad));
         } else {
-            // invalid state
-            println!("Error: Invalid state: Exiting. Line was : '{}'", line_str);
-            return 0;
+            out.push_str(&format!("{}  \"entries\": [\n", pad));
+            for (i, c) in self.nodes[e].entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(",\n");
+                }
+                self.json_entry(*c, indent + 4, out);
+            }
+            out.push_str(&format!("\n{}  ]\n", pad));
         }
+        out.push_str(&format!("{}}}", pad));
     }
 
-    // XXX: Catch last directory here
-    if proc_output && !root.add_dir(&mut cur_dir) {
-        println!("Error: couldn't add dir {:?}", cur_dir);
-        return 0;
+    fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.json_entry(ROOT, 0, &mut out);
+        out.push('\n');
+
+        out
     }
 
-    for e in root.entries.iter() {
-        println!("Entry {}: {} bytes", e.path, e.size());
+    fn tree_entries(&self, d: Inode, prefix: &str, out: &mut String) {
+        let entries = &self.nodes[d].entries;
+        for (i, e) in entries.iter().enumerate() {
+            let last = i == entries.len() - 1;
+            let (branch, indent) = match last {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   ")
+            };
+
+            out.push_str(&format!("{}{}[{:>5}]  {}\n",
+                prefix, branch, human_size(self.size(*e)), self.nodes[*e].name));
+            if self.nodes[*e].is_dir {
+                self.tree_entries(*e, &format!("{}{}", prefix, indent), out);
+            }
+        }
     }
 
-    println!("Root size is {}", root.size());
-    root.print();
+    // A listing in the style of `tree -h`
+    fn to_tree(&self) -> String {
+        let mut out = format!("[{:>5}]  /\n", human_size(self.size(ROOT)));
+        self.tree_entries(ROOT, "", &mut out);
 
-    // XXX: Well then, traverse the tree and save the size of all directories less than 100000
-    // bytes
-    let size_limit = 100000;
+        let dirs = self.nodes.iter().filter(|n| n.is_dir).count() - 1;
+        let files = self.nodes.len() - dirs - 1;
+        out.push_str(&format!("\n{} directories, {} files\n", dirs, files));
 
-    sum_dirs_less_than_size(&root, size_limit)
-}
+        out
+    }
+
+    /*
+     * Recreate the tree on disk under `target`, which must not exist yet
+     * or be empty. Files are created sparse with their listed size, so
+     * they take up almost no space while `du --apparent-size` sees them
+     */
+    fn materialise(&self, target: &Path) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("{}: {}", target.display(), e);
 
-fn sum_dirs_less_than_size(d: &DirEntry, sz_limit: usize) -> usize {
-    let mut rval: usize = 0;
+        if target.exists() && fs::read_dir(target).map_err(err)?.next().is_some() {
+            return Err(format!("{} is not empty", target.display()));
+        }
+        fs::create_dir_all(target).map_err(err)?;
 
-    for e in d.entries.iter() {
-        if e.is_dir {
-            if e.size() < sz_limit {
-                rval += e.size();
+        for (e, _) in self.walk(ROOT) {
+            if e == ROOT {
+                continue;
             }
 
-            rval += sum_dirs_less_than_size(e, sz_limit);
+            let path = target.join(self.path(e).trim_matches('/'));
+            let err = |err: std::io::Error| format!("{}: {}", path.display(), err);
+            if self.nodes[e].is_dir {
+                fs::create_dir(&path).map_err(err)?;
+            } else {
+                let f = File::create(&path).map_err(err)?;
+                f.set_len(self.size(e) as u64).map_err(err)?;
+            }
         }
-    }
 
-    rval
+        Ok(())
+    }
 }
 
-fn part_2() -> usize {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Traversal {
+    // `cd` into each directory right after listing its parent, like the puzzle
+    DepthFirst,
+    // list every directory at one depth before going deeper
+    BreadthFirst
+}
 
-    // track current directory
-    let mut pwd = String::new();
-    let mut proc_output = false;
-    let mut root = DirEntry::new_dir("/");
-    let mut cur_dir = DirEntry::new_dir("/");
+/*
+ * The entries of a real directory, sorted by name, with None as the size
+ * of a directory. Anything that isn't a plain file or directory, or whose
+ * name can't be written on one line of a transcript, is left out
+ */
+fn list_real_dir(path: &Path) -> io::Result<Vec<(String, Option<u64>)>> {
+    let mut entries = Vec::new();
+    for e in fs::read_dir(path)? {
+        let e = e?;
+        let Some(name) = e.file_name().to_str().map(|n| n.to_string()) else {
+            continue;
+        };
+        if name.contains('\n') || name.contains('\r') {
+            continue;
+        }
 
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
-        let line_str = line_str.trim();
+        let meta = fs::symlink_metadata(e.path())?;
+        if meta.is_dir() {
+            entries.push((name, None));
+        } else if meta.is_file() {
+            entries.push((name, Some(meta.len())));
+        }
+    }
+    entries.sort();
 
-        if let Some(dir_str) = line_str.strip_prefix("$ cd ") {
+    Ok(entries)
+}
 
-            // insert previously listed directory into tree
-            if proc_output {
-                if !root.add_dir(&mut cur_dir) {
-                    println!("Error: couldn't add dir {:?}", cur_dir);
-                    return 0;
+/*
+ * Walk a real directory and write the `$ cd` / `$ ls` transcript that
+ * would rebuild it. Directories deeper than max_depth are listed in their
+ * parent but never entered
+ */
+fn capture(root: &Path, order: Traversal, max_depth: Option<usize>) -> io::Result<Vec<String>> {
+    fn ls(path: &Path, lines: &mut Vec<String>) -> io::Result<Vec<String>> {
+        let entries = list_real_dir(path)?;
+        lines.push("$ ls".to_string());
+
+        let mut dirs = Vec::new();
+        for (name, size) in entries {
+            match size {
+                Some(sz) => lines.push(format!("{} {}", sz, name)),
+                None => {
+                    lines.push(format!("dir {}", name));
+                    dirs.push(name);
                 }
-                cur_dir = DirEntry::new_dir("/");
             }
-            proc_output = false;
+        }
 
-            let dir_str = dir_str.trim();
-            if dir_str.is_empty() {
-                // invalid `cd` - no change to state
-                continue;
-            }
+        Ok(dirs)
+    }
 
-            if dir_str.starts_with('/') {
-                pwd.clear();
-                pwd.push_str(dir_str);
+    fn depth_first(
+            path: &Path,
+            depth: usize,
+            max_depth: Option<usize>,
+            lines: &mut Vec<String>) -> io::Result<()> {
+        let dirs = ls(path, lines)?;
+        if max_depth.is_some_and(|max| depth >= max) {
+            return Ok(());
+        }
 
-            } else if dir_str.starts_with("..") {
-                if pwd.len() < 2 {
-                    // no parent directories here!
-                    continue;
-                }
+        for d in dirs {
+            lines.push(format!("$ cd {}", d));
+            depth_first(&path.join(&d), depth + 1, max_depth, lines)?;
+            lines.push("$ cd ..".to_string());
+        }
 
-                // remove trailing slash
-                if pwd.ends_with('/') {
-                    pwd.pop();
-                }
+        Ok(())
+    }
 
-                // find previous directory marker
-                let par_dir = pwd.rfind('/');
+    let mut lines = vec!["$ cd /".to_string()];
+    match order {
+        Traversal::DepthFirst => depth_first(root, 0, max_depth, &mut lines)?,
+        Traversal::BreadthFirst => {
+            // each directory is reached from the root by its path
+            let mut to_visit = VecDeque::from([Vec::<String>::new()]);
+            while let Some(names) = to_visit.pop_front() {
+                if !names.is_empty() {
+                    lines.push("$ cd /".to_string());
+                    for n in names.iter() {
+                        lines.push(format!("$ cd {}", n));
+                    }
+                }
 
-                // truncate string to marker from above
-                if let Some(dir_mark) = par_dir {
-                    pwd.truncate(dir_mark + 1);
+                let path = names.iter().fold(root.to_path_buf(), |p, n| p.join(n));
+                let dirs = ls(&path, &mut lines)?;
+                if max_depth.is_some_and(|max| names.len() >= max) {
+                    continue;
                 }
 
-            } else {
-                pwd.push_str(dir_str);
-                pwd.push('/');
+                for d in dirs {
+                    let mut child = names.clone();
+                    child.push(d);
+                    to_visit.push_back(child);
+                }
             }
+        }
+    }
 
-        } else if line_str.starts_with("$ ls") {
-            proc_output = true;
-            println!("ls called for: {}", pwd);
+    Ok(lines)
+}
 
-            cur_dir = DirEntry::new_dir(&pwd);
+fn read_file(filename: &str) -> Result<Vfs, String> {
+    // Open the file
+    let file = File::open(filename).unwrap();
+    let reader = BufReader::new(file);
 
-        } else if proc_output {
-            let splits: Vec<&str> = line_str.split(' ').collect();
-            if splits.len() != 2 {
-                println!("Error splitting LS output: {}", line_str);
-                return 0;
-            }
-            let mut path = String::from(&pwd);
+    let lines: Vec<String> = reader.lines()
+        .map(|l| l.expect("Couldn't read line as string?"))
+        .collect();
 
-            if line_str.starts_with("dir ") {
-                path.push_str(splits[1]);
+    Vfs::from_transcript(&lines)
+}
 
-                let d = DirEntry::new_dir(&path);
-                println!("  {:?}", d);
+/*
+ * Match a name against a shell-style glob, where `*` matches any run of
+ * characters and `?` matches any one character
+ */
+fn glob_match(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // matches[j] is whether the glob so far matches the first j chars
+    let mut matches = vec![false; name.len() + 1];
+    matches[0] = true;
+    for g in glob.iter() {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match g {
+                '*' => matches[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                c => j > 0 && matches[j - 1] && name[j - 1] == *c
+            };
+        }
+        matches = next;
+    }
 
-                cur_dir.entries.push(d);
+    matches[name.len()]
+}
 
-            } else {
-                let file_size = splits[0].parse().expect("Couldn't parse file size");
-                path.push_str(splits[1]);
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum EntryType {
+    Dir,
+    File
+}
 
-                let f = DirEntry::new_file(&path, file_size);
-                println!("  {:?}", f);
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum SizeTest {
+    LessThan(usize),
+    Exactly(usize),
+    GreaterThan(usize)
+}
 
-                cur_dir.entries.push(f);
-            }
+impl SizeTest {
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parse = |n: &str| n.parse::<usize>()
+            .map_err(|_| format!("Bad size '{}'", s));
 
+        if let Some(n) = s.strip_prefix('-') {
+            Ok(SizeTest::LessThan(parse(n)?))
+        } else if let Some(n) = s.strip_prefix('+') {
+            Ok(SizeTest::GreaterThan(parse(n)?))
         } else {
-            // invalid state
-            println!("Error: Invalid state: Exiting. Line was : '{}'", line_str);
-            return 0;
+            Ok(SizeTest::Exactly(parse(s)?))
         }
     }
 
-    // XXX: Catch last directory here
-    if proc_output && !root.add_dir(&mut cur_dir) {
-        println!("Error: couldn't add dir {:?}", cur_dir);
-        return 0;
+    fn matches(&self, size: usize) -> bool {
+        match self {
+            SizeTest::LessThan(n) => size < *n,
+            SizeTest::Exactly(n) => size == *n,
+            SizeTest::GreaterThan(n) => size > *n
+        }
     }
+}
 
-    // XXX: Well then, traverse the tree and save the size of all directories less than 100000
-    // bytes
-    let space_needed = 30000000;
-    let total_size = 70000000;
-    let size_limit = space_needed - (total_size - root.size());
-    println!("Looking for {} bytes of space", size_limit);
-    find_dir_closest_to_size(&root, size_limit, root.size())
+/*
+ * Questions that can be asked of the filesystem:
+ *
+ *   find [path] [-type d|f] [-size [-|+]N] [-name GLOB]
+ *   du [-d DEPTH] [path]
+ *   largest N [-type d|f] [-name GLOB]
+ *
+ * Sizes are in bytes, and a directory's size includes everything in it
+ */
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum Query {
+    Find {
+        path: String,
+        entry_type: Option<EntryType>,
+        size: Option<SizeTest>,
+        name: Option<String>
+    },
+    Du {
+        path: String,
+        depth: Option<usize>
+    },
+    Largest {
+        count: usize,
+        entry_type: Option<EntryType>,
+        name: Option<String>
+    }
 }
 
-fn find_dir_closest_to_size(d: &DirEntry, sz_limit: usize, cur_lead: usize) -> usize {
-    let mut rval: usize = cur_lead;
+impl Query {
+    fn from_str(s: &str) -> Result<Self, String> {
+        let words: Vec<&str> = s.split_whitespace()
+            .map(|w| w.trim_matches(|c| c == '\'' || c == '"'))
+            .collect();
+        let Some((cmd, args)) = words.split_first() else {
+            return Err("Empty query".to_string());
+        };
+
+        let mut path = None;
+        let mut count = None;
+        let mut entry_type = None;
+        let mut size = None;
+        let mut name = None;
+        let mut depth = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next()
+                .ok_or(format!("Missing value for {}", arg));
+
+            match *arg {
+                "-type" => entry_type = Some(match value()? {
+                    &"d" => EntryType::Dir,
+                    &"f" => EntryType::File,
+                    t => return Err(format!("Unknown type '{}'", t))
+                }),
+                "-size" => size = Some(SizeTest::from_str(value()?)?),
+                "-name" => name = Some(value()?.to_string()),
+                "-d" => depth = Some(value()?.parse()
+                    .map_err(|_| format!("Bad depth in '{}'", s))?),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+                _ if *cmd == "largest" && count.is_none() => count = Some(arg.parse()
+                    .map_err(|_| format!("Bad count '{}'", arg))?),
+                _ if path.is_none() => path = Some(arg.to_string()),
+                _ => return Err(format!("Unexpected '{}' in '{}'", arg, s))
+            }
+        }
 
-    for e in d.entries.iter() {
-        if e.is_dir {
-            if e.size() >= sz_limit && e.size() < rval {
-                rval = e.size();
+        let path = path.unwrap_or("/".to_string());
+        match *cmd {
+            "find" if depth.is_none() => Ok(Query::Find { path, entry_type, size, name }),
+            "du" if entry_type.is_none() && size.is_none() && name.is_none() =>
+                Ok(Query::Du { path, depth }),
+            "largest" if path == "/" && size.is_none() && depth.is_none() => Ok(Query::Largest {
+                count: count.unwrap_or(10),
+                entry_type,
+                name
+            }),
+            "find" | "du" | "largest" => Err(format!("Unsupported options in '{}'", s)),
+            _ => Err(format!("Unknown query '{}'", cmd))
+        }
+    }
+}
 
-                println!("Dir {} took lead with size {}", e.path, rval);
-            }
+impl Vfs {
+    fn entry_matches(
+            &self,
+            e: Inode,
+            entry_type: &Option<EntryType>,
+            name: &Option<String>) -> bool {
+        let is_dir = self.nodes[e].is_dir;
+        let type_ok = match entry_type {
+            Some(EntryType::Dir) => is_dir,
+            Some(EntryType::File) => !is_dir,
+            None => true
+        };
+        // the root has no name of its own, so match it as "/"
+        let entry_name = if e == ROOT { "/" } else { self.nodes[e].name.as_str() };
+        let name_ok = name.as_ref().is_none_or(|g| glob_match(g, entry_name));
+
+        type_ok && name_ok
+    }
 
-            rval = find_dir_closest_to_size(e, sz_limit, rval);
+    // Run a query, returning the matching entries in the order to show them
+    fn query(&self, q: &Query) -> Result<Vec<Inode>, String> {
+        match q {
+            Query::Find { path, entry_type, size, name } => {
+                let top = self.resolve(ROOT, path)?;
+                Ok(self.walk(top).into_iter()
+                    .map(|(e, _)| e)
+                    .filter(|e| self.entry_matches(*e, entry_type, name))
+                    .filter(|e| size.is_none_or(|s| s.matches(self.size(*e))))
+                    .collect())
+            },
+            Query::Du { path, depth } => {
+                let top = self.resolve(ROOT, path)?;
+                // like `du`, children are shown before their parents
+                let mut dirs: Vec<Inode> = self.walk(top).into_iter()
+                    .filter(|(e, d)| self.nodes[*e].is_dir
+                        && depth.is_none_or(|max| *d <= max))
+                    .map(|(e, _)| e)
+                    .collect();
+                dirs.reverse();
+
+                Ok(dirs)
+            },
+            Query::Largest { count, entry_type, name } => {
+                let mut entries: Vec<Inode> = self.walk(ROOT).into_iter()
+                    .map(|(e, _)| e)
+                    .filter(|e| self.entry_matches(*e, entry_type, name))
+                    .collect();
+                entries.sort_by_key(|e| Reverse(self.size(*e)));
+                entries.truncate(*count);
+
+                Ok(entries)
+            }
         }
     }
+}
 
-    rval
+fn run_query(vfs: &Vfs, s: &str) {
+    let result = Query::from_str(s).and_then(|q| vfs.query(&q));
+    match result {
+        Ok(entries) => {
+            for e in entries.iter() {
+                println!("{}\t{}", vfs.size(*e), vfs.path(*e));
+            }
+            let total: usize = entries.iter().map(|e| vfs.size(*e)).sum();
+            println!("{} entries, {} bytes in total", entries.len(), total);
+        },
+        Err(e) => println!("Error: {}", e)
+    }
+}
+
+fn sum_dirs_less_than_size(vfs: &Vfs, sz_limit: usize) -> usize {
+    let q = Query::Find {
+        path: "/".to_string(),
+        entry_type: Some(EntryType::Dir),
+        size: Some(SizeTest::LessThan(sz_limit)),
+        name: None
+    };
+
+    vfs.query(&q).unwrap().iter().map(|d| vfs.size(*d)).sum()
+}
+
+fn find_dir_closest_to_size(vfs: &Vfs, sz_limit: usize) -> usize {
+    let q = Query::Find {
+        path: "/".to_string(),
+        entry_type: Some(EntryType::Dir),
+        size: Some(SizeTest::GreaterThan(sz_limit.saturating_sub(1))),
+        name: None
+    };
+
+    vfs.query(&q).unwrap().iter()
+        .map(|d| vfs.size(*d))
+        .min()
+        .unwrap_or(vfs.size(ROOT))
+}
+
+fn part_1(vfs: &Vfs) -> usize {
+    // traverse the tree and save the size of all directories less than 100000 bytes
+    let size_limit = 100000;
+
+    sum_dirs_less_than_size(vfs, size_limit)
+}
+
+fn part_2(vfs: &Vfs) -> usize {
+    let space_needed = 30000000;
+    let total_size = 70000000;
+    let size_limit = space_needed - (total_size - vfs.size(ROOT));
+    println!("Looking for {} bytes of space", size_limit);
+
+    find_dir_closest_to_size(vfs, size_limit)
 }
 
 fn main() {
+    // "query [QUERY] [file]" runs one query, or reads queries from stdin
+    // "export json|tree [file]" prints the whole tree
+    // "materialise DIR [file]" creates the tree on disk under DIR
+    // "capture DIR [dfs|bfs] [depth]" prints a transcript of a real directory
+    let args: Vec<String> = env::args().collect();
+    let mode = args.get(1).map(|a| a.as_str());
+
+    if let (Some("capture"), Some(dir)) = (mode, args.get(2)) {
+        let order = match args.get(3).map(|o| o.as_str()) {
+            None | Some("dfs") => Traversal::DepthFirst,
+            Some("bfs") => Traversal::BreadthFirst,
+            Some(o) => {
+                println!("Unknown traversal order: {}", o);
+                return;
+            }
+        };
+        let max_depth = args.get(4).map(|d| d.parse().expect("Bad depth"));
+
+        match capture(Path::new(dir), order, max_depth) {
+            Ok(lines) => {
+                for l in lines {
+                    println!("{}", l);
+                }
+            },
+            Err(e) => println!("Error: {}: {}", dir, e)
+        }
+        return;
+    }
+    let filename = match mode {
+        Some(_) => args.get(3).map(|f| f.as_str()).unwrap_or(FILENAME),
+        None => FILENAME
+    };
+
+    let vfs = match read_file(filename) {
+        Ok(vfs) => vfs,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    match (mode, args.get(2).map(|a| a.as_str())) {
+        (None, _) => (),
+        (Some("query"), Some(q)) => {
+            run_query(&vfs, q);
+            return;
+        },
+        (Some("query"), None) => {
+            for line in io::stdin().lock().lines() {
+                let line = line.expect("Couldn't read query from stdin");
+                if !line.trim().is_empty() {
+                    run_query(&vfs, &line);
+                }
+            }
+            return;
+        },
+        (Some("export"), Some("json")) => {
+            print!("{}", vfs.to_json());
+            return;
+        },
+        (Some("export"), Some("tree")) => {
+            print!("{}", vfs.to_tree());
+            return;
+        },
+        (Some("materialise"), Some(dir)) => {
+            match vfs.materialise(Path::new(dir)) {
+                Ok(()) => println!("Created tree under {}", dir),
+                Err(e) => println!("Error: {}", e)
+            }
+            return;
+        },
+        (Some(m), _) => {
+            println!("Unknown mode or missing argument: {}", m);
+            return;
+        }
+    }
+
     println!("Advent of Code, Day 7");
 
-    let answer = part_1();
+    vfs.print();
+    println!("Root size is {}", vfs.size(ROOT));
+
+    let answer = part_1(&vfs);
     println!("First Answer: {:?}", answer);
     assert_eq!(1477771, answer);
 
-    let answer = part_2();
+    let answer = part_2(&vfs);
     println!("Second Answer: {:?}", answer);
     assert_eq!(3579501, answer);
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn transcript(s: &str) -> Vec<String> {
+        s.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let vfs = read_file("./test").unwrap();
+
+        assert_eq!(vfs.size(ROOT), 48381165);
+        assert_eq!(part_1(&vfs), 95437);
+        assert_eq!(part_2(&vfs), 24933642);
+
+        let e = vfs.child(vfs.child(ROOT, "a").unwrap(), "e").unwrap();
+        assert_eq!(vfs.path(e), "/a/e/");
+        assert_eq!(vfs.size(e), 584);
+        assert_eq!(vfs.nodes[e].parent, vfs.child(ROOT, "a"));
+    }
+
+    #[test]
+    fn test_cd() {
+        let mut vfs = read_file("./test").unwrap();
+
+        vfs.cd("/a/e").unwrap();
+        assert_eq!(vfs.path(vfs.cwd), "/a/e/");
+        vfs.cd("../../d").unwrap();
+        assert_eq!(vfs.path(vfs.cwd), "/d/");
+        vfs.cd("/").unwrap();
+        vfs.cd("..").unwrap();
+        assert_eq!(vfs.cwd, ROOT);
+
+        assert!(vfs.cd("/b.txt").is_err());
+        assert!(vfs.cd("/x").is_err());
+    }
+
+    fn query_paths(vfs: &Vfs, q: &str) -> Vec<String> {
+        let q = Query::from_str(q).unwrap();
+        vfs.query(&q).unwrap().iter().map(|e| vfs.path(*e)).collect()
+    }
+
+    #[test]
+    fn test_queries() {
+        let vfs = read_file("./test").unwrap();
+
+        assert_eq!(query_paths(&vfs, "find / -type d -size -100000"), vec!["/a/", "/a/e/"]);
+        assert_eq!(query_paths(&vfs, "find /a -type f"), vec!["/a/e/i", "/a/f", "/a/g", "/a/h.lst"]);
+        assert_eq!(query_paths(&vfs, "find -name '*.*'"),
+            vec!["/a/h.lst", "/b.txt", "/c.dat", "/d/d.log", "/d/d.ext"]);
+        assert_eq!(query_paths(&vfs, "find / -size 584"), vec!["/a/e/", "/a/e/i"]);
+        assert_eq!(query_paths(&vfs, "du -d 1"), vec!["/d/", "/a/", "/"]);
+        assert_eq!(query_paths(&vfs, "du /a"), vec!["/a/e/", "/a/"]);
+        assert_eq!(query_paths(&vfs, "largest 2 -type f"), vec!["/b.txt", "/c.dat"]);
+        assert_eq!(query_paths(&vfs, "largest 3 -name d*"), vec!["/d/", "/d/d.log", "/d/d.ext"]);
+
+        for bad in ["", "ls /", "find -type x", "du -d", "largest many", "find -size 1k",
+                "du -name a"] {
+            assert!(Query::from_str(bad).is_err(), "{}", bad);
+        }
+        assert!(vfs.query(&Query::from_str("find /x").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_exports() {
+        let lines = transcript("$ cd /\n$ ls\ndir a\n2048 \"b\".txt\n$ cd a\n$ ls\n5 c");
+        let vfs = Vfs::from_transcript(&lines).unwrap();
+
+        assert_eq!(vfs.to_json(), r#"{
+  "name": "/",
+  "type": "dir",
+  "size": 2053,
+  "entries": [
+    {
+      "name": "a",
+      "type": "dir",
+      "size": 5,
+      "entries": [
+        {
+          "name": "c",
+          "type": "file",
+          "size": 5
+        }
+      ]
+    },
+    {
+      "name": "\"b\".txt",
+      "type": "file",
+      "size": 2048
+    }
+  ]
+}
+"#);
+
+        assert_eq!(vfs.to_tree(), "[ 2.0K]  /
+├── [   5B]  a
+│   └── [   5B]  c
+└── [ 2.0K]  \"b\".txt
+
+1 directories, 2 files
+");
+
+        assert_eq!(human_size(1023), "1023B");
+        assert_eq!(human_size(48381165), "46M");
+    }
+
+    #[test]
+    fn test_materialise() {
+        let vfs = read_file("./test").unwrap();
+        let target = env::temp_dir().join(format!("day-7-test-{}", std::process::id()));
+
+        vfs.materialise(&target).unwrap();
+        assert_eq!(fs::metadata(target.join("b.txt")).unwrap().len(), 14848514);
+        assert_eq!(fs::metadata(target.join("a/e/i")).unwrap().len(), 584);
+        assert!(fs::metadata(target.join("d")).unwrap().is_dir());
+
+        // won't write over an existing tree
+        assert!(vfs.materialise(&target).is_err());
+
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn test_capture() {
+        let vfs = read_file("./test").unwrap();
+        let target = env::temp_dir().join(format!("day-7-capture-{}", std::process::id()));
+        vfs.materialise(&target).unwrap();
+
+        let dfs = capture(&target, Traversal::DepthFirst, None).unwrap();
+        assert_eq!(dfs[..7], transcript("$ cd /\n$ ls\ndir a\n14848514 b.txt\n8504156 c.dat\n\
+            dir d\n$ cd a")[..]);
+
+        // both orders rebuild the same tree
+        let all_entries = |vfs: &Vfs| {
+            let mut entries: Vec<(String, usize)> = vfs.walk(ROOT).iter()
+                .map(|(e, _)| (vfs.path(*e), vfs.size(*e)))
+                .collect();
+            entries.sort();
+            entries
+        };
+        for lines in [dfs, capture(&target, Traversal::BreadthFirst, None).unwrap()] {
+            let captured = Vfs::from_transcript(&lines).unwrap();
+            assert_eq!(all_entries(&captured), all_entries(&vfs));
+        }
+
+        // /a/e is listed in /a, but not entered
+        let lines = capture(&target, Traversal::BreadthFirst, Some(1)).unwrap();
+        let captured = Vfs::from_transcript(&lines).unwrap();
+        assert_eq!(captured.size(ROOT), vfs.size(ROOT) - 584);
+        assert!(!lines.contains(&"$ cd e".to_string()));
+
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*.txt", "b.txt"));
+        assert!(!glob_match("*.txt", "b.txt.gz"));
+        assert!(glob_match("?.*", "d.log"));
+        assert!(!glob_match("?", "ab"));
+        assert!(glob_match("a*b*c", "aXbYbc"));
+    }
+
+    #[test]
+    fn test_inconsistent_transcripts() {
+        // same file listed with two sizes
+        let lines = transcript("$ cd /\n$ ls\n10 a\n$ ls\n20 a");
+        assert!(Vfs::from_transcript(&lines).is_err());
+
+        // cd into a dir that was never listed
+        let lines = transcript("$ cd /\n$ ls\ndir a\n$ cd b");
+        assert!(Vfs::from_transcript(&lines).is_err());
+
+        // the same listing twice is fine
+        let lines = transcript("$ cd /\n$ ls\n10 a\ndir b\n$ ls\n10 a\ndir b");
+        let vfs = Vfs::from_transcript(&lines).unwrap();
+        assert_eq!(vfs.size(ROOT), 10);
+        assert_eq!(vfs.nodes[ROOT].entries.len(), 2);
+
+        // names that would move around the tree
+        let lines = transcript("$ cd /\n$ ls\n10 ../a");
+        assert!(Vfs::from_transcript(&lines).is_err());
+    }
+}
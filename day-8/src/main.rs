@@ -1,12 +1,25 @@
-use std::fs::File;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::env;
+use std::fs::{self, File};
 use std::io::{prelude::*, BufReader};
 
 const FILENAME: &str = "./input";
 
+// The directions a tree can be seen from, and that it can look towards
+const UP: usize = 0;
+const RIGHT: usize = 1;
+const DOWN: usize = 2;
+const LEFT: usize = 3;
+
 #[derive(Debug)]
 struct Tree {
     height: u32,
     visible: bool,
+    // whether the tree can be seen from outside the grid in each direction
+    visible_from: [bool; 4],
+    // number of trees the tree can see in each direction
+    view: [usize; 4],
 }
 
 impl Tree {
@@ -14,23 +27,28 @@ impl Tree {
         Self {
             height,
             visible: false,
+            visible_from: [false; 4],
+            view: [0; 4],
         }
     }
+
+    fn scenic_score(&self) -> usize {
+        self.view.iter().product()
+    }
 }
 
 // read in the file and store in a 2-D vector
-fn read_tree_grid() -> Vec::<Vec::<Tree>> {
+fn read_tree_grid(filename: &str) -> Vec::<Vec::<Tree>> {
     let mut rows = Vec::<Vec::<Tree>>::new();
 
     // Open the file
-    let file = File::open(FILENAME).unwrap();
+    let file = File::open(filename).unwrap();
     let reader = BufReader::new(file);
 
     // Read file line by line
     for line in reader.lines() {
         let line_str = line.expect("Couldn't read line as string?");
         let line_str = line_str.trim();
-        println!("{}", line_str);
 
         let mut row = Vec::<Tree>::new();
         for c in line_str.chars() {
@@ -44,212 +62,294 @@ fn read_tree_grid() -> Vec::<Vec::<Tree>> {
     rows
 }
 
-fn mark_visible_trees(mut trees: Vec::<Vec::<Tree>>) -> Vec::<Vec::<Tree>> {
-    let mut sum = 0;
-    let num_rows = trees.len();
-    let row_len = trees[0].len();
-
-    // walk the top side
-    for y in 0..row_len {
-        let mut h = 0;
-        for (x, row) in  trees.iter_mut().enumerate() {
-            let t: &mut Tree = &mut row[y];
-
-            // tree is visible if it is taller than previously seen tallest tree
-            if t.height >= h {
-                if !t.visible {
-                    println!("Tree[{}][{}]({}) visible; (prev: {})", x, y, t.height, h);
-                    t.visible = true;
-                    sum += 1;
-                }
-
-                if t.height < 9 {
-                    h = t.height + 1
-                } else {
-                    // once we hit the max height, no more trees are visible
-                    break;
-                }
+/*
+ * Walk one line of trees, given as grid coordinates in walking order, and
+ * fill in what each tree sees looking back the way we came (`dir`).
+ *
+ * A tree is visible from that edge if it is taller than every tree before
+ * it. For viewing distance, a stack holds the trees before this one that
+ * aren't hidden behind a later tree at least as tall, so the stack is in
+ * decreasing height order. Trees shorter than this one can't block any
+ * later tree that this one doesn't, so they are popped; whatever is left on
+ * top is the tree that blocks this one's view. Each tree is pushed and
+ * popped once, so the whole line is O(length).
+ */
+fn sweep(trees: &mut [Vec::<Tree>], line: &[(usize, usize)], dir: usize) {
+    let mut tallest: Option<u32> = None;
+    let mut stack: Vec<usize> = Vec::new();
+
+    for (i, (x, y)) in line.iter().enumerate() {
+        let t = &mut trees[*x][*y];
+
+        t.visible_from[dir] = tallest.is_none_or(|h| t.height > h);
+        t.visible |= t.visible_from[dir];
+        tallest = tallest.max(Some(t.height));
+
+        while let Some(top) = stack.last() {
+            let (tx, ty) = line[*top];
+            if trees[tx][ty].height >= trees[*x][*y].height {
+                break;
             }
+            stack.pop();
         }
+        trees[*x][*y].view[dir] = match stack.last() {
+            Some(top) => i - top,
+            None => i
+        };
+        stack.push(i);
     }
+}
 
-    // walk the right side
-    for (x, row) in  trees.iter_mut().enumerate() {
-        let mut h = 0;
-        for y in (0..row_len).rev() {
-            let t: &mut Tree = &mut row[y];
-
-            // tree is visible if it is taller than previously seen tallest tree
-            if t.height >= h {
-                if !t.visible {
-                    println!("Tree[{}][{}]({}) visible; (prev: {})", x, y, t.height, h);
-                    t.visible = true;
-                    sum += 1;
-                }
+/*
+ * Fill in the visibility and viewing distances of every tree, with one
+ * sweep in each direction along every row and column: O(rows * cols)
+ */
+fn analyse_forest(trees: &mut [Vec::<Tree>]) {
+    let num_rows = trees.len();
+    let row_len = trees.first().map(|r| r.len()).unwrap_or(0);
 
-                if t.height < 9 {
-                    h = t.height + 1
-                } else {
-                    // once we hit the max height, no more trees are visible
-                    break;
-                }
-            }
-        }
+    for x in 0..num_rows {
+        let mut line: Vec<(usize, usize)> = (0..row_len).map(|y| (x, y)).collect();
+        sweep(trees, &line, LEFT);
+        line.reverse();
+        sweep(trees, &line, RIGHT);
     }
 
-    // walk the bottom side
-    for y in (0..row_len).rev() {
-        let mut h = 0;
-        for x in (0..num_rows).rev() {
-            let t: &mut Tree = &mut trees[x][y];
-
-            // tree is visible if it is taller than previously seen tallest tree
-            if t.height >= h {
-                if !t.visible {
-                    println!("Tree[{}][{}]({}) visible; (prev: {})", x, y, t.height, h);
-                    t.visible = true;
-                    sum += 1;
-                }
-
-                if t.height < 9 {
-                    h = t.height + 1
-                } else {
-                    // once we hit the max height, no more trees are visible
-                    break;
-                }
-            }
-        }
+    for y in 0..row_len {
+        let mut line: Vec<(usize, usize)> = (0..num_rows).map(|x| (x, y)).collect();
+        sweep(trees, &line, UP);
+        line.reverse();
+        sweep(trees, &line, DOWN);
     }
+}
 
-    println!("walk the left side");
-    for x in (0..num_rows).rev() {
-        let mut h = 0;
-        for y in 0..row_len {
-            let t: &mut Tree = &mut trees[x][y];
-
-            // tree is visible if it is taller than previously seen tallest tree
-            if t.height >= h {
-                if !t.visible {
-                    println!("Tree[{}][{}]({}) visible; (prev: {})", x, y, t.height, h);
-                    t.visible = true;
-                    sum += 1;
-                }
+fn sum_visible_trees(trees: &[Vec::<Tree>]) -> u32 {
+    trees.iter().flatten().filter(|t| t.visible).count() as u32
+}
 
-                if t.height < 9 {
-                    h = t.height + 1
-                } else {
-                    // once we hit the max height, no more trees are visible
-                    break;
-                }
-            }
-        }
-    }
-    println!("Marked {} trees as visible", sum);
+fn find_max_vis_score(trees: &[Vec::<Tree>]) -> usize {
+    trees.iter().flatten()
+        .map(|t| t.scenic_score())
+        .max()
+        .unwrap_or(0)
+}
+
+const DIRECTION_NAMES: [char; 4] = ['U', 'R', 'D', 'L'];
 
-    trees
+// Shades from lowest to highest scenic score
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+// The directions a tree is visible from, as "UR.." for up and right
+fn visible_directions(t: &Tree) -> String {
+    DIRECTION_NAMES.iter().zip(t.visible_from.iter())
+        .map(|(c, v)| if *v { *c } else { '.' })
+        .collect()
 }
 
-fn sum_visible_trees(trees: Vec::<Vec::<Tree>>) -> u32 {
-    let mut sum = 0;
+/*
+ * Scenic scores grow as the product of four distances, so a log scale
+ * keeps the middling trees from all looking the same. Returns a level in
+ * 0..=max_level
+ */
+fn score_level(score: usize, max_score: usize, max_level: usize) -> usize {
+    if max_score == 0 {
+        return 0;
+    }
 
-    let trees = mark_visible_trees(trees);
+    let level = (score as f64).ln_1p() / (max_score as f64).ln_1p();
+    (level * max_level as f64).round() as usize
+}
 
-    for row in trees.iter() {
-        for tree in row.iter() {
-            if tree.visible {
-                sum += 1;
-            }
+fn print_report(trees: &[Vec::<Tree>]) {
+    println!("{:>4} {:>4} {:>6} {:>7} {:>5} {:>5} {:>5} {:>5} {:>8}",
+        "row", "col", "height", "visible", "up", "right", "down", "left", "score");
+    for (x, row) in trees.iter().enumerate() {
+        for (y, t) in row.iter().enumerate() {
+            println!("{:>4} {:>4} {:>6} {:>7} {:>5} {:>5} {:>5} {:>5} {:>8}",
+                x, y, t.height, visible_directions(t),
+                t.view[UP], t.view[RIGHT], t.view[DOWN], t.view[LEFT],
+                t.scenic_score());
         }
     }
+}
+
+fn heatmap(trees: &[Vec::<Tree>]) -> String {
+    let max_score = find_max_vis_score(trees);
 
+    let mut out = String::new();
     for row in trees.iter() {
-        println!("{:?}", row);
+        for t in row.iter() {
+            let level = score_level(t.scenic_score(), max_score, SHADES.len() - 1);
+            out.push(SHADES[level] as char);
+        }
+        out.push('\n');
     }
 
-    sum
+    out
 }
 
-fn part_1() -> u32 {
+// The heatmap as a plain (P2) PGM image, one pixel per tree
+fn heatmap_pgm(trees: &[Vec::<Tree>]) -> String {
+    let max_score = find_max_vis_score(trees);
+    let row_len = trees.first().map(|r| r.len()).unwrap_or(0);
 
-    let trees = read_tree_grid();
+    let mut out = format!("P2\n{} {}\n255\n", row_len, trees.len());
+    for row in trees.iter() {
+        let pixels: Vec<String> = row.iter()
+            .map(|t| score_level(t.scenic_score(), max_score, 255).to_string())
+            .collect();
+        out.push_str(&pixels.join(" "));
+        out.push('\n');
+    }
 
-    // Print the answer to the first part
-    let answer = sum_visible_trees(trees);
-    println!("First Answer: {:?}", answer);
+    out
+}
 
-    answer
+// The best treehouse spots as (row, col, score), best first
+fn top_locations(trees: &[Vec::<Tree>], n: usize) -> Vec<(usize, usize, usize)> {
+    let mut locations: Vec<(usize, usize, usize)> = trees.iter().enumerate()
+        .flat_map(|(x, row)| row.iter().enumerate()
+            .map(move |(y, t)| (x, y, t.scenic_score())))
+        .collect();
+    locations.sort_by_key(|(x, y, score)| (Reverse(*score), *x, *y));
+    locations.truncate(n);
+
+    locations
 }
 
-fn calc_vis_score(trees: &[Vec::<Tree>], x: usize, y: usize) -> usize {
-    let mut score = 1;
-    let t = &trees[x][y];
+/*
+ * Someone looking out over the forest from a grid position, which can be
+ * outside the grid or in one of its trees, with their eyes at `eye` height
+ */
+#[derive(Debug,Clone,Copy)]
+struct Observer {
+    row: i64,
+    col: i64,
+    eye: f64,
+}
 
-    // look up
-    let mut s = 0;
-    for row in trees.iter().take(x).rev() {
-        s += 1;
-        if row[y].height >= t.height {
-            break;
+impl Observer {
+    fn tree_height(trees: &[Vec::<Tree>], row: i64, col: i64) -> Option<u32> {
+        if row < 0 || col < 0 {
+            return None;
         }
+
+        trees.get(row as usize)
+            .and_then(|r| r.get(col as usize))
+            .map(|t| t.height)
     }
-    score *= s;
-
-    // look right
-    let mut s = 0;
-    for t2 in trees[x].iter().skip(y+1) {
-        s += 1;
-        if t2.height >= t.height {
-            break;
+
+    /*
+     * Whether the top of the tree at (row, col) can be seen. Trees are
+     * columns filling their grid square, and the sight line runs from the
+     * centre of the observer's square to the centre of the target's. Any
+     * square the line passes through blocks it if the tree there is at
+     * least as tall as the line anywhere in that square; a line through a
+     * corner only passes through the diagonal squares.
+     */
+    fn can_see(&self, trees: &[Vec::<Tree>], row: usize, col: usize) -> bool {
+        let top = trees[row][col].height as f64;
+        let (dx, dy) = (row as i64 - self.row, col as i64 - self.col);
+        let (nx, ny) = (dx.abs(), dy.abs());
+        if nx == 0 && ny == 0 {
+            return false;
         }
-    }
-    score *= s;
-
-    // look down
-    let mut s = 0;
-    for row in trees.iter().skip(x+1) {
-        s += 1;
-        if row[y].height >= t.height {
-            break;
+
+        // Step to the next square along the line, returning it and the
+        // fraction of the way along the line where it is entered
+        let step = |ix: i64, iy: i64| {
+            // compare the crossings (1 + 2ix) / 2nx and (1 + 2iy) / 2ny
+            let x_cross = (1 + 2 * ix) * ny;
+            let y_cross = (1 + 2 * iy) * nx;
+            if nx > 0 && (ny == 0 || x_cross <= y_cross) {
+                let t = (1 + 2 * ix) as f64 / (2 * nx) as f64;
+                if x_cross == y_cross {
+                    (ix + 1, iy + 1, t)
+                } else {
+                    (ix + 1, iy, t)
+                }
+            } else {
+                (ix, iy + 1, (1 + 2 * iy) as f64 / (2 * ny) as f64)
+            }
+        };
+        let line_height = |t: f64| self.eye + (top - self.eye) * t;
+
+        let (mut ix, mut iy, mut t_in) = step(0, 0);
+        while (ix, iy) != (nx, ny) {
+            let (next_x, next_y, t_out) = step(ix, iy);
+
+            // the line is lowest at one end of its path through the square
+            let low = line_height(t_in).min(line_height(t_out));
+            let square = Observer::tree_height(
+                trees, self.row + dx.signum() * ix, self.col + dy.signum() * iy);
+            if square.is_some_and(|h| h as f64 >= low) {
+                return false;
+            }
+
+            (ix, iy, t_in) = (next_x, next_y, t_out);
         }
+
+        true
     }
-    score *= s;
-
-    // look right
-    let mut s = 0;
-    for t2 in trees[x].iter().take(y).rev() {
-        s += 1;
-        if t2.height >= t.height {
-            break;
-        }
+
+    // Trees in any of the 8 compass directions whose tops can be seen
+    fn visible_along_rays(&self, trees: &[Vec::<Tree>]) -> BTreeSet<(usize, usize)> {
+        self.visible_where(trees, |dx, dy| dx == 0 || dy == 0 || dx.abs() == dy.abs())
     }
-    score *= s;
 
-    score
-}
+    // Every tree whose top can be seen
+    fn visible_line_of_sight(&self, trees: &[Vec::<Tree>]) -> BTreeSet<(usize, usize)> {
+        self.visible_where(trees, |_, _| true)
+    }
 
-fn find_max_vis_score(trees: &[Vec::<Tree>]) -> usize {
-    let mut score = 0;
-    let num_rows = trees.len();
-    let row_len = trees[0].len();
+    fn visible_where(
+            &self,
+            trees: &[Vec::<Tree>],
+            wanted: fn(i64, i64) -> bool) -> BTreeSet<(usize, usize)> {
+        let mut visible = BTreeSet::new();
+        for (x, row) in trees.iter().enumerate() {
+            for y in 0..row.len() {
+                let (dx, dy) = (x as i64 - self.row, y as i64 - self.col);
+                if wanted(dx, dy) && self.can_see(trees, x, y) {
+                    visible.insert((x, y));
+                }
+            }
+        }
 
-    for x in 1..num_rows-1 {
-        for y in 1..row_len-1 {
-            let s = calc_vis_score(trees, x, y);
-            if s > score {
-                score = s;
+        visible
+    }
+}
+
+// The grid with visible trees shown by height, hidden ones as '.' and the observer as 'O'
+fn render_view(trees: &[Vec::<Tree>], observer: &Observer, visible: &BTreeSet<(usize, usize)>) -> String {
+    let mut out = String::new();
+    for (x, row) in trees.iter().enumerate() {
+        for (y, t) in row.iter().enumerate() {
+            if (x as i64, y as i64) == (observer.row, observer.col) {
+                out.push('O');
+            } else if visible.contains(&(x, y)) {
+                out.push(char::from_digit(t.height, 10).unwrap_or('#'));
+            } else {
+                out.push('.');
             }
         }
+        out.push('\n');
     }
 
-    score
+    out
 }
 
-fn part_2() -> usize {
+fn part_1(trees: &[Vec::<Tree>]) -> u32 {
+    // Print the answer to the first part
+    let answer = sum_visible_trees(trees);
+    println!("First Answer: {:?}", answer);
 
-    let trees = read_tree_grid();
+    answer
+}
 
+fn part_2(trees: &[Vec::<Tree>]) -> usize {
     // Print the answer to the second part
-    let answer = find_max_vis_score(&trees);
+    let answer = find_max_vis_score(trees);
     println!("Second Answer: {:?}", answer);
 
     answer
@@ -258,7 +358,210 @@ fn part_2() -> usize {
 fn main() {
     println!("Advent of Code, Day 8");
 
-    assert_eq!(part_1(), 1789);
-    assert_eq!(part_2(), 314820);
+    // "report [file]" prints every tree, "heatmap [file]" shades scores,
+    // "pgm OUT [file]" saves the heatmap as an image, "top N [file]" lists
+    // the best treehouse spots, "observe ROW COL EYE [rays|los] [file]"
+    // shows what can be seen from one spot
+    let args: Vec<String> = env::args().collect();
+    let mode = args.get(1).map(|a| a.as_str());
+    let file_arg = match mode {
+        Some("pgm") | Some("top") => 3,
+        Some("observe") => 6,
+        _ => 2
+    };
+    let filename = args.get(file_arg).map(|f| f.as_str()).unwrap_or(FILENAME);
+
+    let mut trees = read_tree_grid(filename);
+    analyse_forest(&mut trees);
+
+    match mode {
+        None => (),
+        Some("report") => {
+            print_report(&trees);
+            return;
+        },
+        Some("heatmap") => {
+            print!("{}", heatmap(&trees));
+            return;
+        },
+        Some("pgm") => {
+            let out = args.get(2).expect("No output file given");
+            fs::write(out, heatmap_pgm(&trees)).expect("Couldn't write image");
+            println!("Wrote heatmap to {}", out);
+            return;
+        },
+        Some("top") => {
+            let n = args.get(2).map(|n| n.parse().expect("Bad count")).unwrap_or(10);
+            for (x, y, score) in top_locations(&trees, n) {
+                println!("row {}, col {}: score {}", x, y, score);
+            }
+            return;
+        },
+        Some("observe") => {
+            let arg = |i: usize| args.get(i).expect("Usage: observe ROW COL EYE [rays|los] [file]");
+            let observer = Observer {
+                row: arg(2).parse().expect("Bad row"),
+                col: arg(3).parse().expect("Bad col"),
+                eye: arg(4).parse().expect("Bad eye height")
+            };
+            let visible = match args.get(5).map(|m| m.as_str()) {
+                None | Some("los") => observer.visible_line_of_sight(&trees),
+                Some("rays") => observer.visible_along_rays(&trees),
+                Some(m) => {
+                    println!("Unknown view mode: {}", m);
+                    return;
+                }
+            };
+            print!("{}", render_view(&trees, &observer, &visible));
+            println!("{} trees visible", visible.len());
+            return;
+        },
+        Some(m) => {
+            println!("Unknown mode: {}", m);
+            return;
+        }
+    }
+
+    assert_eq!(part_1(&trees), 1789);
+    assert_eq!(part_2(&trees), 314820);
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // Heights from a simple LCG, so the forests are the same every run
+    fn generate_forest(num_rows: usize, row_len: usize, seed: u64) -> Vec::<Vec::<Tree>> {
+        let mut state = seed;
+        (0..num_rows).map(|_| (0..row_len).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            Tree::from_height(((state >> 33) % 10) as u32)
+        }).collect()).collect()
+    }
+
+    // The direct approach: walk outwards from every tree
+    fn naive_view(trees: &[Vec::<Tree>], x: usize, y: usize) -> ([bool; 4], [usize; 4]) {
+        let h = trees[x][y].height;
+        let up: Vec<u32> = (0..x).rev().map(|i| trees[i][y].height).collect();
+        let right: Vec<u32> = (y + 1..trees[x].len()).map(|i| trees[x][i].height).collect();
+        let down: Vec<u32> = (x + 1..trees.len()).map(|i| trees[i][y].height).collect();
+        let left: Vec<u32> = (0..y).rev().map(|i| trees[x][i].height).collect();
+
+        let mut visible = [false; 4];
+        let mut view = [0; 4];
+        for (dir, line) in [(UP, up), (RIGHT, right), (DOWN, down), (LEFT, left)] {
+            visible[dir] = line.iter().all(|t| *t < h);
+            view[dir] = match line.iter().position(|t| *t >= h) {
+                Some(i) => i + 1,
+                None => line.len()
+            };
+        }
+
+        (visible, view)
+    }
+
+    #[test]
+    fn test_example() {
+        let mut trees = read_tree_grid("./test");
+        analyse_forest(&mut trees);
+
+        assert_eq!(sum_visible_trees(&trees), 21);
+        assert_eq!(find_max_vis_score(&trees), 8);
+        assert_eq!(trees[3][2].view, [2, 2, 1, 2]);
+        assert_eq!(trees[1][2].view, [1, 2, 2, 1]);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut trees = read_tree_grid("./test");
+        analyse_forest(&mut trees);
+
+        assert_eq!(visible_directions(&trees[1][1]), "U..L");
+        assert_eq!(visible_directions(&trees[2][2]), "....");
+
+        assert_eq!(heatmap(&trees), "     \n -#- \n %-= \n -@* \n     \n");
+        assert!(heatmap_pgm(&trees).starts_with("P2\n5 5\n255\n0 0 0 0 0\n0 80 187 80 0\n"));
+        assert_eq!(top_locations(&trees, 3), vec![(3, 2, 8), (2, 1, 6), (1, 2, 4)]);
+    }
+
+    #[test]
+    fn test_observer() {
+        let trees = read_tree_grid("./test");
+
+        // from the treehouse at row 3, col 2, with eyes at the top of the tree
+        let observer = Observer { row: 3, col: 2, eye: 5.0 };
+        let rays = observer.visible_along_rays(&trees);
+        let los = observer.visible_line_of_sight(&trees);
+        assert!(rays.is_subset(&los));
+        assert_eq!(render_view(&trees, &observer, &rays), "\
+.....
+..5..
+.533.
+33O49
+.539.
+");
+        assert_eq!(render_view(&trees, &observer, &los), "\
+...73
+..5..
+6533.
+33O49
+.539.
+");
+
+        // from outside the top left corner, up high enough to see everything
+        let observer = Observer { row: -1, col: -1, eye: 100.0 };
+        assert_eq!(observer.visible_line_of_sight(&trees).len(), 25);
+        assert_eq!(observer.visible_along_rays(&trees).len(), 5);
+    }
+
+    #[test]
+    fn test_observer_flat_forest() {
+        let trees: Vec<Vec<Tree>> = (0..7)
+            .map(|_| (0..7).map(|_| Tree::from_height(0)).collect())
+            .collect();
+
+        // standing in the middle, every tree is below eye level
+        let observer = Observer { row: 3, col: 3, eye: 1.0 };
+        assert_eq!(observer.visible_along_rays(&trees).len(), 24);
+        assert_eq!(observer.visible_line_of_sight(&trees).len(), 48);
+
+        // with eyes on the ground, only the trees next to us can be seen
+        let observer = Observer { row: 3, col: 3, eye: 0.0 };
+        assert_eq!(observer.visible_line_of_sight(&trees).len(), 8);
+    }
+
+    #[test]
+    fn test_matches_naive() {
+        for (num_rows, row_len, seed) in [(1, 1, 1), (1, 30, 2), (30, 1, 3), (57, 83, 4)] {
+            let mut trees = generate_forest(num_rows, row_len, seed);
+            analyse_forest(&mut trees);
+
+            for x in 0..num_rows {
+                for y in 0..row_len {
+                    let (visible, view) = naive_view(&trees, x, y);
+                    assert_eq!(trees[x][y].visible_from, visible);
+                    assert_eq!(trees[x][y].view, view);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_forests() {
+        // a million trees is fine when every sweep is linear
+        let mut trees = generate_forest(1000, 1000, 5);
+        analyse_forest(&mut trees);
+        assert!(sum_visible_trees(&trees) >= 4 * 1000 - 4);
+
+        // one tall tree in the middle of a flat forest can see to every edge
+        let n = 1501;
+        let mut trees: Vec<Vec<Tree>> = (0..n)
+            .map(|_| (0..n).map(|_| Tree::from_height(0)).collect())
+            .collect();
+        trees[n / 2][n / 2].height = 9;
+        analyse_forest(&mut trees);
+        assert_eq!(trees[n / 2][n / 2].view, [n / 2; 4]);
+        assert_eq!(find_max_vis_score(&trees), (n / 2).pow(4));
+    }
+}
@@ -1,6 +1,7 @@
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
-use std::hash::Hash;
 use std::collections::HashSet;
 
 const FILENAME: &str = "./input";
@@ -16,146 +17,442 @@ struct Coord {
     y: i32,
 }
 
-fn move_head(mut head: Coord, dir: &str, mag: i32) -> Coord {
-    match dir {
-        "R" => head.x += mag,
-        "L" => head.x -= mag,
-        "U" => head.y += mag,
-        "D" => head.y -= mag,
-        _ => println!("ERROR: invalid move '{}'", dir),
+impl Coord {
+    const ORIGIN: Coord = Coord { x: 0, y: 0 };
+
+    fn offset(&self, by: Coord) -> Coord {
+        Coord { x: self.x + by.x, y: self.y + by.y }
+    }
+
+    fn touching(&self, other: &Coord) -> bool {
+        (self.x - other.x).abs() <= 1 && (self.y - other.y).abs() <= 1
     }
-    head
 }
 
-fn follow(tail: &mut Coord, head: &Coord) {
-    let x_diff: i32 = head.x - tail.x;
-    let y_diff: i32 = head.y - tail.y;
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum MoveError {
+    // Not a direction and a distance separated by a space
+    Format(String),
+    // Not one of R, L, U and D, or a diagonal pair of them such as UR
+    Direction(String),
+    // Not a whole number of steps
+    Distance(String),
+}
 
-    if x_diff.abs() == 2 {
-        tail.x += x_diff / 2;
-        if y_diff.abs() > 0 {
-            tail.y += y_diff / y_diff.abs();
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::Format(s) => write!(f, "invalid move '{}'", s),
+            MoveError::Direction(s) => write!(f, "invalid direction '{}'", s),
+            MoveError::Distance(s) => write!(f, "invalid distance '{}'", s),
         }
+    }
+}
+
+// A bad move, with the line of the file it was on
+#[derive(Debug,Clone,PartialEq,Eq)]
+struct LineError {
+    line: usize,
+    error: MoveError,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.error)
+    }
+}
+
+// A number of unit steps of the head, straight or diagonally
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct Move {
+    step: Coord,
+    distance: u32,
+}
 
-    } else if y_diff.abs() == 2 {
-        tail.y += y_diff / 2;
-        if x_diff.abs() > 0 {
-            tail.x += x_diff / x_diff.abs();
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let vertical = match self.step.y { 1 => "U", -1 => "D", _ => "" };
+        let horizontal = match self.step.x { 1 => "R", -1 => "L", _ => "" };
+        write!(f, "{}{} {}", vertical, horizontal, self.distance)
+    }
+}
+
+impl Move {
+    fn from_str(line: &str) -> Result<Move, MoveError> {
+        let (dir, distance) = line.trim().split_once(' ')
+            .ok_or_else(|| MoveError::Format(line.to_string()))?;
+
+        let mut step = Coord::ORIGIN;
+        let mut axes = (false, false);
+        for c in dir.chars() {
+            let (axis, by) = match c {
+                'R' => (&mut axes.0, Coord { x: 1, y: 0 }),
+                'L' => (&mut axes.0, Coord { x: -1, y: 0 }),
+                'U' => (&mut axes.1, Coord { x: 0, y: 1 }),
+                'D' => (&mut axes.1, Coord { x: 0, y: -1 }),
+                _ => return Err(MoveError::Direction(dir.to_string())),
+            };
+            // at most one step along each axis
+            if std::mem::replace(axis, true) {
+                return Err(MoveError::Direction(dir.to_string()));
+            }
+            step = step.offset(by);
+        }
+        if step == Coord::ORIGIN {
+            return Err(MoveError::Direction(dir.to_string()));
         }
-    } else if y_diff.abs() > 2 || x_diff.abs() > 2 {
-        panic!("FATAL ERROR: Coords are too distant: {:?} {:?}", tail, head);
+
+        let distance = distance.parse()
+            .map_err(|_| MoveError::Distance(distance.to_string()))?;
+
+        Ok(Move { step, distance })
     }
 }
 
-fn move_from_line(line_str: String) -> (String, i32) {
-    let splits: Vec<&str> = line_str.split(' ').collect();
+// How a knot moves when the knot in front of it is no longer touching it
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum FollowRule {
+    // One step towards the knot in front, diagonally if not in line with it
+    Pull,
+    // Into the position the knot in front has just left, like a snake
+    Trail,
+}
+
+impl FollowRule {
+    fn from_str(s: &str) -> Result<FollowRule, String> {
+        match s {
+            "pull" => Ok(FollowRule::Pull),
+            "trail" => Ok(FollowRule::Trail),
+            _ => Err(format!("Unknown follow rule '{}'", s)),
+        }
+    }
 
-    if splits.len() == 2 {
-        let dir = splits[0];
-        let mag: i32 = splits[1].parse().expect("Couldn't parse move magnitude");
+    // Where a knot moves to after the knot in front moves from `from` to `to`
+    fn follow(&self, knot: Coord, from: Coord, to: Coord) -> Coord {
+        if knot.touching(&to) {
+            return knot;
+        }
 
-        (String::from(dir), mag)
-    } else {
-        println!("ERROR: Line was not valid '{}'", line_str);
-        (String::from("X"), 0)
+        match self {
+            FollowRule::Pull => Coord {
+                x: knot.x + (to.x - knot.x).signum(),
+                y: knot.y + (to.y - knot.y).signum(),
+            },
+            FollowRule::Trail => from,
+        }
     }
 }
 
+/*
+ * A rope of knots, the first being the head, which all start at the
+ * origin. Every position each knot has been in is kept and, when the
+ * history is kept, where the whole rope was after each step
+ */
+struct Rope {
+    rule: FollowRule,
+    knots: Vec<Coord>,
+    visited: Vec<HashSet<Coord>>,
+    keep_history: bool,
+    history: Vec<Vec<Coord>>,
+}
+
+impl Rope {
+    fn new(knots: usize, rule: FollowRule, history: bool) -> Rope {
+        assert!(knots > 0, "A rope needs at least one knot");
+
+        Rope {
+            rule,
+            knots: vec![Coord::ORIGIN; knots],
+            visited: vec![HashSet::from([Coord::ORIGIN]); knots],
+            keep_history: history,
+            history: if history { vec![vec![Coord::ORIGIN; knots]] } else { Vec::new() },
+        }
+    }
+
+    // Move the head by one step and let the rest of the rope follow it
+    fn step(&mut self, by: Coord) {
+        let mut from = self.knots[0];
+        self.knots[0] = from.offset(by);
+        self.visited[0].insert(self.knots[0]);
+
+        for i in 1..self.knots.len() {
+            let knot = self.knots[i];
+            let moved = self.rule.follow(knot, from, self.knots[i - 1]);
+            if moved == knot {
+                // nothing further down the rope will move either
+                break;
+            }
+
+            from = knot;
+            self.knots[i] = moved;
+            self.visited[i].insert(moved);
+        }
+
+        if self.keep_history {
+            self.history.push(self.knots.clone());
+        }
+    }
+
+    fn apply(&mut self, m: &Move) {
+        for _ in 0..m.distance {
+            self.step(m.step);
+        }
+    }
+
+    // Number of positions the knot has been in, the head being knot 0
+    fn visited_count(&self, knot: usize) -> usize {
+        self.visited[knot].len()
+    }
+
+    // The corners of the smallest box around everywhere the rope has been
+    fn bounds(&self) -> (Coord, Coord) {
+        let mut min = Coord::ORIGIN;
+        let mut max = Coord::ORIGIN;
+        for c in self.visited.iter().flatten() {
+            min = Coord { x: min.x.min(c.x), y: min.y.min(c.y) };
+            max = Coord { x: max.x.max(c.x), y: max.y.max(c.y) };
+        }
+
+        (min, max)
+    }
+
+    /*
+     * Draw the rope as in the puzzle text, with up at the top: H for the
+     * head, then each knot by number, s for the start, # for where the tail
+     * has been. Where knots overlap, the one nearest the head is shown
+     */
+    fn render(&self, min: Coord, max: Coord) -> String {
+        let tail = self.knots.len() - 1;
+        let mut out = String::new();
+        for y in (min.y..=max.y).rev() {
+            for x in min.x..=max.x {
+                let c = Coord { x, y };
+                let ch = match self.knots.iter().position(|k| *k == c) {
+                    Some(0) => 'H',
+                    Some(i) => char::from_digit(i as u32, 10).unwrap_or('*'),
+                    None if c == Coord::ORIGIN => 's',
+                    None if self.visited[tail].contains(&c) => '#',
+                    None => '.',
+                };
+                out.push(ch);
+            }
+            out.push('\n');
+        }
+
+        out
+    }
 
-fn dedup<T: Eq + Hash + Copy>(v: &mut Vec<T>) { // note the Copy constraint
-    let mut uniques = HashSet::new();
-    v.retain(|e| uniques.insert(*e));
+    // Where every knot was after each step, one row per knot per step
+    fn trajectory_csv(&self) -> String {
+        let mut csv = String::from("step,knot,x,y\n");
+        for (step, knots) in self.history.iter().enumerate() {
+            for (knot, c) in knots.iter().enumerate() {
+                csv.push_str(&format!("{},{},{},{}\n", step, knot, c.x, c.y));
+            }
+        }
+
+        csv
+    }
 }
 
-fn part_1() {
+fn read_moves<R: BufRead>(reader: R) -> Result<Vec<Move>, LineError> {
+    reader.lines()
+        .map(|line| line.expect("Couldn't read line as string?"))
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Move::from_str(&line)
+            .map_err(|error| LineError { line: i + 1, error }))
+        .collect()
+}
+
+fn load_moves(filename: &str) -> Result<Vec<Move>, LineError> {
     // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
-
-    // init state for answer
-    let mut head = Coord {
-        x: 0,
-        y: 0,
-    };
-    let mut tail = Coord {
-        x: 0,
-        y: 0,
-    };
-    let mut tail_pos = Vec::<Coord>::new();
-
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
-        let (dir, mag) = move_from_line(line_str);
-
-        for _ in 0..mag {
-            head = move_head(head, &dir, 1);
-            follow(&mut tail, &head);
-            tail_pos.push(Coord{
-                x: tail.x,
-                y: tail.y
-            })
-        }
+    let file = File::open(filename).unwrap();
+    read_moves(BufReader::new(file))
+}
+
+// Run a rope through the moves in the file, reporting any bad lines
+fn simulate(filename: &str, knots: usize, rule: FollowRule, history: bool) -> Result<Rope, LineError> {
+    let mut rope = Rope::new(knots, rule, history);
+    for m in load_moves(filename)? {
+        rope.apply(&m);
     }
 
+    Ok(rope)
+}
+
+// Draw the rope after each move, all at the same size
+fn print_frames(filename: &str, knots: usize, rule: FollowRule) -> Result<(), LineError> {
+    let moves = load_moves(filename)?;
+    let (min, max) = simulate(filename, knots, rule, false)?.bounds();
+
+    let mut rope = Rope::new(knots, rule, false);
+    println!("== Initial State ==\n\n{}", rope.render(min, max));
+    for m in moves {
+        rope.apply(&m);
+        println!("== {} ==\n\n{}", m, rope.render(min, max));
+    }
+
+    Ok(())
+}
+
+fn tail_positions(filename: &str, knots: usize) -> usize {
+    let rope = simulate(filename, knots, FollowRule::Pull, false)
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    rope.visited_count(knots - 1)
+}
+
+fn part_1() {
+    let answer = tail_positions(FILENAME, 2);
+
     // Print the answer to the first part
-    dedup(&mut tail_pos);
-    let answer = tail_pos.len();
     println!("First Answer: {:?}", answer);
 }
 
 
 fn part_2() {
-    // Open the file
-    let file = File::open(FILENAME).unwrap();
-    let reader = BufReader::new(file);
-
-    // init state for answer
-    let mut head = Coord {
-        x: 0,
-        y: 0,
-    };
-
-    // make this a vector of 9 tails
-    let mut tails: Vec<Coord> = Vec::new();
-    for _ in 0..9 {
-        tails.push(Coord {
-            x: 0,
-            y: 0,
-        });
-    }
-    let mut tail_pos = Vec::<Coord>::new();
-
-    // Read file line by line
-    for line in reader.lines() {
-        let line_str = line.expect("Couldn't read line as string?");
-        let (dir, mag) = move_from_line(line_str);
-
-        for _ in 0..mag {
-            head = move_head(head, &dir, 1);
-
-            let mut prev: &Coord = &head;
-            for tail in tails.iter_mut() {
-                follow(tail, prev);
-                prev = tail;
-            }
-            tail_pos.push(Coord{
-                x: tails[8].x,
-                y: tails[8].y
-            });
-        }
-    }
+    let answer = tail_positions(FILENAME, 10);
 
     // Print the answer to the second part
-    dedup(&mut tail_pos);
-    let answer = tail_pos.len();
     println!("Second Answer: {:?}", answer);
 }
 
 fn main() {
+    // "rope KNOTS [pull|trail] [file]" counts the positions of every knot,
+    // "frames" draws the rope after each move and "csv" prints the path
+    // of every knot, taking the same arguments
+    let args: Vec<String> = env::args().collect();
+    let mode = args.get(1).map(|a| a.as_str());
+    if let Some(m @ ("rope" | "frames" | "csv")) = mode {
+        let knots = args.get(2).map(|k| k.parse().expect("Bad knot count"))
+            .unwrap_or(10);
+        let rule = args.get(3).map(|r| FollowRule::from_str(r).unwrap())
+            .unwrap_or(FollowRule::Pull);
+        let filename = args.get(4).map(|f| f.as_str()).unwrap_or(FILENAME);
+
+        let result = match m {
+            "frames" => print_frames(filename, knots, rule),
+            "csv" => simulate(filename, knots, rule, true)
+                .map(|rope| print!("{}", rope.trajectory_csv())),
+            _ => simulate(filename, knots, rule, false).map(|rope| {
+                for (i, knot) in rope.knots.iter().enumerate() {
+                    println!("Knot {}: at ({}, {}), visited {}",
+                        i, knot.x, knot.y, rope.visited_count(i));
+                }
+            }),
+        };
+        if let Err(e) = result {
+            println!("ERROR: {}", e);
+        }
+        return;
+    }
+
     println!("Advent of Code, Day 9");
 
     part_1();
     part_2();
 }
 
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(tail_positions("./test", 2), 13);
+        assert_eq!(tail_positions("./test", 10), 1);
+        assert_eq!(tail_positions("./test2", 10), 36);
+
+        // the final positions of the larger example
+        let rope = simulate("./test2", 10, FollowRule::Pull, false).unwrap();
+        assert_eq!(rope.knots[0], Coord { x: -11, y: 15 });
+        assert_eq!(rope.knots[9], Coord { x: -11, y: 6 });
+        assert!(rope.history.is_empty());
+    }
+
+    #[test]
+    fn test_moves() {
+        assert_eq!(Move::from_str("UR 3"), Ok(Move { step: Coord { x: 1, y: 1 }, distance: 3 }));
+        assert_eq!(Move::from_str("LD 1"), Ok(Move { step: Coord { x: -1, y: -1 }, distance: 1 }));
+        assert_eq!(Move::from_str("R"), Err(MoveError::Format("R".to_string())));
+        assert_eq!(Move::from_str("X 2"), Err(MoveError::Direction("X".to_string())));
+        assert_eq!(Move::from_str("LR 2"), Err(MoveError::Direction("LR".to_string())));
+        assert_eq!(Move::from_str("UU 2"), Err(MoveError::Direction("UU".to_string())));
+        assert_eq!(Move::from_str("U -2"), Err(MoveError::Distance("-2".to_string())));
+
+        // a diagonal head drags a straight rope along behind it
+        let mut rope = Rope::new(4, FollowRule::Pull, false);
+        rope.apply(&Move::from_str("UR 5").unwrap());
+        assert_eq!(rope.knots, vec![
+            Coord { x: 5, y: 5 }, Coord { x: 4, y: 4 }, Coord { x: 3, y: 3 }, Coord { x: 2, y: 2 }
+        ]);
+        assert_eq!(rope.visited_count(3), 3);
+
+        // bad lines are reported by their place in the file, blanks included
+        let moves = read_moves("R 2\n\nU 1\nX 3\n".as_bytes());
+        assert_eq!(moves, Err(LineError { line: 4, error: MoveError::Direction("X".to_string()) }));
+    }
+
+    #[test]
+    fn test_render() {
+        let rope = simulate("./test", 2, FollowRule::Pull, false).unwrap();
+        let (min, max) = rope.bounds();
+        assert_eq!((min, max), (Coord { x: 0, y: 0 }, Coord { x: 5, y: 4 }));
+        assert_eq!(rope.render(min, max), "\
+..##..
+...##.
+.1H##.
+....#.
+s###..
+");
+
+        // the larger example part way through, after "R 5" and "U 8"
+        let mut rope = Rope::new(10, FollowRule::Pull, true);
+        for m in ["R 5", "U 8"] {
+            rope.apply(&Move::from_str(m).unwrap());
+        }
+        assert_eq!(rope.render(Coord { x: -11, y: -5 }, Coord { x: 14, y: 15 })
+            .lines().skip(7).take(9).collect::<Vec<_>>(), vec![
+                "................H.........",
+                "................1.........",
+                "................2.........",
+                "................3.........",
+                "...............54.........",
+                "..............6...........",
+                ".............7............",
+                "............8.............",
+                "...........9..............",
+            ]);
+
+        let csv = rope.trajectory_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 14 * 10);
+        assert_eq!(lines[1], "0,0,0,0");
+        assert_eq!(lines[lines.len() - 1], "13,9,0,0");
+        assert_eq!(Move::from_str("DL 2").unwrap().to_string(), "DL 2");
+    }
+
+    #[test]
+    fn test_follow_rules() {
+        // with a single knot behind the head the rules agree
+        let input = load_moves("./input").unwrap();
+        let mut pull = Rope::new(2, FollowRule::Pull, false);
+        let mut trail = Rope::new(2, FollowRule::Trail, false);
+        for m in input.iter() {
+            pull.apply(m);
+            trail.apply(m);
+        }
+        assert_eq!(pull.visited, trail.visited);
+
+        // but further down, a trailing rope retraces the head's path
+        let mut rope = Rope::new(4, FollowRule::Trail, false);
+        for m in ["R 3", "U 2", "L 2"] {
+            rope.apply(&Move::from_str(m).unwrap());
+        }
+        assert_eq!(rope.knots, vec![
+            Coord { x: 1, y: 2 }, Coord { x: 2, y: 2 }, Coord { x: 3, y: 1 }, Coord { x: 2, y: 0 }
+        ]);
+    }
+}